@@ -0,0 +1,391 @@
+//! Command APDU encoding and response APDU parsing
+//!
+
+use crate::StatusWord;
+use core::fmt;
+
+/// Maximum command data length of a short APDU
+pub const DATA_MAX: usize = 255;
+
+/// Maximum command APDU length with up to 255 data bytes, extended Le
+pub const APDU_BUF_SIZE: usize = 4 + 3 + DATA_MAX + 2;
+
+/// Maximum expected response length of a short APDU
+const LE_MAX: u16 = 256;
+
+/// CLA bit announcing more commands of the chain to follow
+const CLA_CHAINING: u8 = 0x10;
+
+/// Command APDU, ISO/IEC 7816-4 cases 1 to 4. Extended length encoding
+/// is used when the data is longer than 255 bytes or Le is above 256.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Apdu<'d> {
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &'d [u8],
+    le: Option<u16>,
+}
+
+/// Response APDU split into data and status words SW1-SW2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Response<'r> {
+    data: &'r [u8],
+    sw1: u8,
+    sw2: u8,
+}
+
+/// APDU encoding or parsing error
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ApduError {
+    /// Command data is longer than 65535 bytes
+    DataTooLong(usize),
+
+    /// Output buffer is too small for the encoded command
+    BufTooSmall(usize, usize),
+
+    /// Response is shorter than the 2 status bytes
+    RespTooShort(usize),
+
+    /// Application identifier is not 5 to 16 bytes long
+    AidLen(usize),
+}
+
+impl fmt::Display for ApduError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DataTooLong(n) => write!(f, "command data of {n} bytes is too long"),
+            Self::BufTooSmall(n, len) => {
+                write!(f, "buffer of {n} bytes is too small for {len} bytes")
+            }
+            Self::RespTooShort(n) => write!(f, "response of {n} bytes has no status words"),
+            Self::AidLen(n) => write!(f, "invalid AID length {n}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ApduError {}
+
+impl<'d> Apdu<'d> {
+    /// Create case 1 command APDU with header only
+    pub fn new(cla: u8, ins: u8, p1: u8, p2: u8) -> Self {
+        Self {
+            cla,
+            ins,
+            p1,
+            p2,
+            data: &[],
+            le: None,
+        }
+    }
+
+    /// Set command data, sent with the Lc byte when not empty
+    pub fn data(mut self, data: &'d [u8]) -> Self {
+        self.data = data;
+
+        self
+    }
+
+    /// Set expected response length. Zero means the maximum, 256 bytes
+    /// for short and 65536 bytes for extended encoding.
+    pub fn le(mut self, le: u16) -> Self {
+        self.le = Some(le);
+
+        self
+    }
+
+    /// Class byte CLA
+    pub fn cla(&self) -> u8 {
+        self.cla
+    }
+
+    /// Command data length
+    pub fn data_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Intermediate command of a chain with `DATA_MAX` data bytes from
+    /// `off`, the chaining bit set in CLA and no Le
+    pub(crate) fn chain_part(&self, off: usize) -> Self {
+        Self {
+            cla: self.cla | CLA_CHAINING,
+            data: &self.data[off..off + DATA_MAX],
+            le: None,
+            ..*self
+        }
+    }
+
+    /// Last command of a chain with the data from `off`
+    pub(crate) fn chain_last(&self, off: usize) -> Self {
+        Self {
+            data: &self.data[off..],
+            ..*self
+        }
+    }
+
+    /// Check if the command needs extended length encoding
+    pub fn is_extended(&self) -> bool {
+        self.data.len() > DATA_MAX || self.le.is_some_and(|le| le > LE_MAX)
+    }
+
+    /// Encoded command APDU length
+    pub fn encoded_len(&self) -> usize {
+        let (lc_len, le_len) = if self.is_extended() { (3, 2) } else { (1, 1) };
+
+        let lc = if self.data.is_empty() {
+            0
+        } else {
+            lc_len + self.data.len()
+        };
+
+        let le = match self.le {
+            Some(_) if self.is_extended() && self.data.is_empty() => 3,
+            Some(_) => le_len,
+            None => 0,
+        };
+
+        4 + lc + le
+    }
+
+    /// Encode command APDU into `buf`, returning the encoded length
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, ApduError> {
+        if self.data.len() > usize::from(u16::MAX) {
+            return Err(ApduError::DataTooLong(self.data.len()));
+        }
+
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(ApduError::BufTooSmall(buf.len(), len));
+        }
+
+        buf[..4].copy_from_slice(&[self.cla, self.ins, self.p1, self.p2]);
+        let mut n = 4;
+
+        let ext = self.is_extended();
+        if ext {
+            buf[n] = 0x00;
+            n += 1;
+        }
+
+        if !self.data.is_empty() {
+            let lc = self.data.len() as u16;
+            if ext {
+                buf[n..n + 2].copy_from_slice(&lc.to_be_bytes());
+                n += 2;
+            } else {
+                buf[n] = lc as u8;
+                n += 1;
+            }
+            buf[n..n + self.data.len()].copy_from_slice(self.data);
+            n += self.data.len();
+        }
+
+        // Le = 0x00 or 0x0000 stands for the maximum length
+        if let Some(le) = self.le {
+            if ext {
+                buf[n..n + 2].copy_from_slice(&le.to_be_bytes());
+                n += 2;
+            } else {
+                buf[n] = le as u8;
+                n += 1;
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+impl<'r> Response<'r> {
+    /// Split raw response APDU into data and status words
+    pub fn parse(raw: &'r [u8]) -> Result<Self, ApduError> {
+        match raw {
+            [data @ .., sw1, sw2] => Ok(Self {
+                data,
+                sw1: *sw1,
+                sw2: *sw2,
+            }),
+            _ => Err(ApduError::RespTooShort(raw.len())),
+        }
+    }
+
+    /// Response data without status words
+    pub fn data(&self) -> &'r [u8] {
+        self.data
+    }
+
+    /// Status words SW1-SW2
+    pub fn sw(&self) -> u16 {
+        u16::from_be_bytes([self.sw1, self.sw2])
+    }
+
+    /// Status word SW1
+    pub fn sw1(&self) -> u8 {
+        self.sw1
+    }
+
+    /// Status word SW2
+    pub fn sw2(&self) -> u8 {
+        self.sw2
+    }
+
+    /// Interpreted status words
+    pub fn status(&self) -> StatusWord {
+        StatusWord::new(self.sw1, self.sw2)
+    }
+
+    /// Check for normal processing status, 9000 or 61XX
+    pub fn is_success(&self) -> bool {
+        self.status().is_success()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(apdu: &Apdu) -> ([u8; APDU_BUF_SIZE], usize) {
+        let mut buf = [0u8; APDU_BUF_SIZE];
+        let n = apdu.write(&mut buf).expect("Encoding failed");
+        assert_eq!(n, apdu.encoded_len());
+
+        (buf, n)
+    }
+
+    #[test]
+    fn test_case1() {
+        let (buf, n) = encode(&Apdu::new(0x00, 0x70, 0x80, 0x01));
+        assert_eq!(&buf[..n], &[0x00, 0x70, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_case2() {
+        let (buf, n) = encode(&Apdu::new(0x80, 0xca, 0x9f, 0x7f).le(0x2d));
+        assert_eq!(&buf[..n], &[0x80, 0xca, 0x9f, 0x7f, 0x2d]);
+    }
+
+    #[test]
+    fn test_case2_le_max() {
+        let (buf, n) = encode(&Apdu::new(0x00, 0xb0, 0x00, 0x00).le(0));
+        assert_eq!(&buf[..n], &[0x00, 0xb0, 0x00, 0x00, 0x00]);
+
+        let (buf, n) = encode(&Apdu::new(0x00, 0xb0, 0x00, 0x00).le(256));
+        assert_eq!(&buf[..n], &[0x00, 0xb0, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_case3() {
+        let (buf, n) = encode(&Apdu::new(0x00, 0xa4, 0x04, 0x00).data(&[0xa0, 0x00]));
+        assert_eq!(&buf[..n], &[0x00, 0xa4, 0x04, 0x00, 0x02, 0xa0, 0x00]);
+    }
+
+    #[test]
+    fn test_case4() {
+        let apdu = Apdu::new(0x00, 0xa4, 0x04, 0x00).data(&[0xa0]).le(0);
+        let (buf, n) = encode(&apdu);
+        assert_eq!(&buf[..n], &[0x00, 0xa4, 0x04, 0x00, 0x01, 0xa0, 0x00]);
+    }
+
+    #[test]
+    fn test_case4_data_max() {
+        let data = [0x5a; 255];
+        let (buf, n) = encode(&Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data).le(256));
+
+        assert_eq!(n, 4 + 1 + 255 + 1);
+        assert_eq!(buf[4], 0xff);
+        assert_eq!(&buf[5..260], &data);
+        assert_eq!(buf[260], 0x00);
+    }
+
+    #[test]
+    fn test_data_too_long() {
+        let data = [0u8; 65536];
+        let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data);
+
+        assert_eq!(
+            apdu.write(&mut [0u8; 8]),
+            Err(ApduError::DataTooLong(65536))
+        );
+    }
+
+    #[test]
+    fn test_case2_extended() {
+        let apdu = Apdu::new(0x00, 0xb0, 0x00, 0x00).le(257);
+        assert!(apdu.is_extended());
+
+        let (buf, n) = encode(&apdu);
+        assert_eq!(&buf[..n], &[0x00, 0xb0, 0x00, 0x00, 0x00, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn test_case3_extended() {
+        let data = [0x5a; 256];
+        let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data);
+        let mut buf = [0u8; 263];
+
+        assert_eq!(apdu.write(&mut buf), Ok(263));
+        assert_eq!(apdu.encoded_len(), 263);
+        assert_eq!(&buf[..7], &[0x00, 0xd6, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(&buf[7..], &data);
+    }
+
+    #[test]
+    fn test_case4_extended() {
+        let apdu = Apdu::new(0x00, 0xa4, 0x04, 0x00).data(&[0xa0]).le(0x1234);
+        let (buf, n) = encode(&apdu);
+
+        assert_eq!(n, 10);
+        assert_eq!(
+            &buf[..n],
+            &[0x00, 0xa4, 0x04, 0x00, 0x00, 0x00, 0x01, 0xa0, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn test_case4_extended_le_max() {
+        let data = [0x5a; 300];
+        let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data).le(0);
+        let mut buf = [0u8; 309];
+
+        assert_eq!(apdu.write(&mut buf), Ok(309));
+        assert_eq!(&buf[4..7], &[0x00, 0x01, 0x2c]);
+        assert_eq!(&buf[307..], &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_response_parse() {
+        let resp = Response::parse(&[0x9f, 0x7f, 0x90, 0x00]).unwrap();
+
+        assert_eq!(resp.data(), &[0x9f, 0x7f]);
+        assert_eq!(resp.sw(), 0x9000);
+        assert_eq!(resp.sw1(), 0x90);
+        assert_eq!(resp.sw2(), 0x00);
+        assert!(resp.is_success());
+    }
+
+    #[test]
+    fn test_response_status_only() {
+        let resp = Response::parse(&[0x6a, 0x82]).unwrap();
+
+        assert!(resp.data().is_empty());
+        assert_eq!(resp.sw(), 0x6a82);
+        assert!(!resp.is_success());
+        assert!(Response::parse(&[0x61, 0x10]).unwrap().is_success());
+        assert_eq!(resp.status(), StatusWord::FileNotFound);
+    }
+
+    #[test]
+    fn test_response_too_short() {
+        assert_eq!(Response::parse(&[0x90]), Err(ApduError::RespTooShort(1)));
+        assert_eq!(Response::parse(&[]), Err(ApduError::RespTooShort(0)));
+    }
+
+    #[test]
+    fn test_buf_too_small() {
+        let apdu = Apdu::new(0x00, 0xa4, 0x04, 0x00).data(&[0xa0, 0x00]);
+
+        assert_eq!(apdu.write(&mut [0u8; 6]), Err(ApduError::BufTooSmall(6, 7)));
+    }
+}
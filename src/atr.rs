@@ -0,0 +1,195 @@
+//! Answer To Reset (ATR) parsing
+//!
+
+/// Assumed card clock frequency in kHz for waiting times computation
+pub const CARD_CLOCK_KHZ: u32 = 3570;
+
+/// Default clock rate conversion integer Fd
+const FD_DEFAULT: u32 = 372;
+
+/// Initial character TS of the direct convention
+const TS_DIRECT: u8 = 0x3b;
+
+/// Initial character TS of the inverse convention
+const TS_INVERSE: u8 = 0x3f;
+
+/// Bit convention of the characters, indicated by the initial character TS
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Convention {
+    /// TS = 0x3B: logic one is high level, least significant bit first
+    Direct,
+
+    /// TS = 0x3F: logic one is low level, most significant bit first
+    Inverse,
+
+    /// TS is absent or invalid
+    Unknown,
+}
+
+/// Information extracted from the Answer To Reset
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AtrInfo {
+    /// Initial character TS, 0 if the ATR is parsed without it
+    pub ts: u8,
+
+    /// Offered protocols mask, bit N is set for protocol T=N
+    pub protocols: u16,
+
+    /// Information field size for the card, the first TA byte for T=1
+    pub ifsc: Option<u8>,
+
+    /// Block waiting time integer, high nibble of the first TB byte for T=1
+    pub bwi: Option<u8>,
+
+    /// Character waiting time integer, low nibble of the first TB byte
+    /// for T=1
+    pub cwi: Option<u8>,
+
+    /// Check byte TCK is valid or absent
+    pub tck_valid: bool,
+}
+
+impl AtrInfo {
+    /// Check if the card offers T=1 protocol
+    pub fn t1(&self) -> bool {
+        self.protocols & 2 != 0
+    }
+
+    /// Initial character TS, 0 if the ATR is parsed without it
+    pub fn ts_byte(&self) -> u8 {
+        self.ts
+    }
+
+    /// Bit convention indicated by the initial character TS
+    pub fn convention(&self) -> Convention {
+        match self.ts {
+            TS_DIRECT => Convention::Direct,
+            TS_INVERSE => Convention::Inverse,
+            _ => Convention::Unknown,
+        }
+    }
+
+    /// Check if TS indicates the direct convention
+    pub fn is_direct_convention(&self) -> bool {
+        self.convention() == Convention::Direct
+    }
+
+    /// Check if TS indicates the inverse convention
+    pub fn is_inverse_convention(&self) -> bool {
+        self.convention() == Convention::Inverse
+    }
+
+    /// Block waiting time in milliseconds, rounded up. Computed for the
+    /// default Fd = 372 and the card clock of `CARD_CLOCK_KHZ`.
+    pub fn bwt(&self) -> Option<u32> {
+        match self.bwi {
+            Some(bwi @ 0..=9) => {
+                let etu = 11 + (960 << bwi);
+                Some((etu * FD_DEFAULT).div_ceil(CARD_CLOCK_KHZ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse the Answer To Reset starting with the format byte T0.
+///
+/// The initial character TS is not part of the input, as in the
+/// S(RESET) response. T=0 is assumed when no protocol is indicated.
+pub fn parse_atr(atr: &[u8]) -> AtrInfo {
+    let mut y = match atr.first() {
+        Some(t0) => i32::from(*t0),
+        None => 0,
+    };
+    let mut tck = y as u8;
+    // Protocol of the last TD byte, the low nibble of T0 is the number
+    // of historical bytes
+    let mut t = None;
+    let mut proto = 0u16;
+    let mut ifsc = None;
+    let mut tb = None;
+
+    for it in atr.iter().skip(1) {
+        let c = *it;
+        tck ^= c;
+
+        if y & 0xf0 == 0x80 {
+            y = c.into();
+            t = Some(c & 15);
+            proto |= 1 << (c & 15);
+        } else if y >= 16 {
+            if ifsc.is_none() && t == Some(1) && y & 0x1f == 0x11 {
+                ifsc = Some(c);
+            }
+            if tb.is_none() && t == Some(1) && y & 0x3f == 0x21 {
+                tb = Some(c);
+            }
+            y &= y - 16;
+        } else {
+            y = -1;
+        }
+    }
+
+    if proto == 0 {
+        proto = 1;
+    }
+
+    AtrInfo {
+        ts: 0,
+        protocols: proto,
+        ifsc,
+        bwi: tb.map(|b| b >> 4),
+        cwi: tb.map(|b| b & 15),
+        tck_valid: !atr.is_empty() && (!tck_present(proto) || tck == 0),
+    }
+}
+
+/// Parse the Answer To Reset starting with the initial character TS, e.g.
+/// as received on a character mode interface.
+pub fn parse_atr_with_ts(atr: &[u8]) -> AtrInfo {
+    match atr.split_first() {
+        Some((ts, rest)) => AtrInfo {
+            ts: *ts,
+            ..parse_atr(rest)
+        },
+        None => parse_atr(atr),
+    }
+}
+
+/// Compute the expected check byte TCK of the ATR starting with TS.
+///
+/// The bytes from T0 up to the byte before TCK are XORed, all bytes after
+/// TS if TCK is absent.
+pub fn compute_tck(atr: &[u8]) -> u8 {
+    let end = if has_tck(atr) {
+        atr.len() - 1
+    } else {
+        atr.len()
+    };
+
+    atr.get(1..end)
+        .unwrap_or_default()
+        .iter()
+        .fold(0, |tck, c| tck ^ c)
+}
+
+/// Validate the check byte TCK of the ATR starting with TS.
+///
+/// TCK is absent if only T=0 is indicated, such ATR is always valid.
+pub fn validate_tck(atr: &[u8]) -> bool {
+    if atr.len() < 2 {
+        return false;
+    }
+
+    !has_tck(atr) || atr.last() == Some(&compute_tck(atr))
+}
+
+fn has_tck(atr: &[u8]) -> bool {
+    atr.len() > 2 && tck_present(parse_atr(&atr[1..]).protocols)
+}
+
+fn tck_present(protocols: u16) -> bool {
+    protocols & !1 != 0
+}
@@ -0,0 +1,559 @@
+//! T=1 block encoding and decoding
+//!
+//! A block consists of the prologue field (NAD, PCB and LEN bytes), the
+//! information field INF of LEN bytes and the epilogue field with the
+//! error detection code EDC.
+
+use core::fmt;
+
+/// Prologue field length, NAD, PCB and LEN bytes
+pub const HEADER_LEN: usize = 3;
+
+/// Maximum information field length
+pub const INF_MAX: usize = 254;
+
+/// Block error detection code
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ChkAlgo {
+    /// Longitudinal redundancy check, 1 byte
+    #[default]
+    Lrc,
+
+    /// CRC-16 CCITT, 2 bytes sent most significant byte first unless
+    /// `little_endian` is set
+    Crc { little_endian: bool },
+}
+
+impl ChkAlgo {
+    /// Error detection code length in bytes
+    pub fn edc_len(self) -> usize {
+        match self {
+            Self::Lrc => 1,
+            Self::Crc { .. } => 2,
+        }
+    }
+}
+
+/// Block type encoded in the protocol control byte (PCB)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockKind {
+    /// Information block
+    I,
+
+    /// Receive ready block
+    R,
+
+    /// Supervisory block
+    S,
+}
+
+impl BlockKind {
+    /// Block type bits of the protocol control byte
+    pub fn code(self) -> u8 {
+        match self {
+            Self::I => 0x00,
+            Self::R => 0x80,
+            Self::S => 0xc0,
+        }
+    }
+}
+
+impl fmt::Display for BlockKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::I => "I-block",
+            Self::R => "R-block",
+            Self::S => "S-block",
+        })
+    }
+}
+
+/// S-block type, the low 5 bits of PCB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SBlockRequest {
+    /// Resynchronization, S(RESYNCH)
+    Resynch,
+
+    /// Information field size, S(IFS)
+    Ifs,
+
+    /// Chain abortion, S(ABORT)
+    Abort,
+
+    /// Waiting time extension, S(WTX)
+    Wtx,
+
+    /// Programming voltage error, S(VPP error)
+    VppError,
+
+    /// Soft reset, S(RESET)
+    Reset,
+
+    /// Unknown type
+    Other(u8),
+}
+
+impl SBlockRequest {
+    /// Decode the S-block type from the protocol control byte
+    pub fn from_pcb(pcb: u8) -> Self {
+        match pcb & 0x1f {
+            0x00 => Self::Resynch,
+            0x01 => Self::Ifs,
+            0x02 => Self::Abort,
+            0x03 => Self::Wtx,
+            0x04 => Self::VppError,
+            0x05 => Self::Reset,
+            code => Self::Other(code),
+        }
+    }
+
+    /// S-block type code, the low 5 bits of PCB
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Resynch => 0x00,
+            Self::Ifs => 0x01,
+            Self::Abort => 0x02,
+            Self::Wtx => 0x03,
+            Self::VppError => 0x04,
+            Self::Reset => 0x05,
+            Self::Other(code) => code & 0x1f,
+        }
+    }
+}
+
+/// S-block direction, bit 6 of PCB is set for a response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SBlockDir {
+    /// Request block
+    Request,
+
+    /// Response block
+    Response,
+}
+
+impl fmt::Display for SBlockRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Resynch => f.write_str("RESYNCH"),
+            Self::Ifs => f.write_str("IFS"),
+            Self::Abort => f.write_str("ABORT"),
+            Self::Wtx => f.write_str("WTX"),
+            Self::VppError => f.write_str("VPP error"),
+            Self::Reset => f.write_str("RESET"),
+            Self::Other(code) => write!(f, "{code:#04x}"),
+        }
+    }
+}
+
+impl fmt::Display for SBlockDir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Request => "request",
+            Self::Response => "response",
+        })
+    }
+}
+
+/// S-block type and direction, displayed as e.g. `S(IFS request)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SBlock {
+    /// S-block type
+    pub req: SBlockRequest,
+
+    /// Request or response
+    pub dir: SBlockDir,
+}
+
+impl SBlock {
+    /// Decode the S-block type and direction from the protocol control
+    /// byte
+    pub fn from_pcb(pcb: u8) -> Self {
+        Self {
+            req: SBlockRequest::from_pcb(pcb),
+            dir: if pcb & 0x20 != 0 {
+                SBlockDir::Response
+            } else {
+                SBlockDir::Request
+            },
+        }
+    }
+
+    /// Protocol control byte of the S-block
+    pub fn pcb(self) -> u8 {
+        sblock_pcb(self.req, self.dir)
+    }
+}
+
+impl fmt::Display for SBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "S({} {})", self.req, self.dir)
+    }
+}
+
+/// Decoded view of a raw block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockView<'b> {
+    /// Node address byte
+    pub nad: u8,
+
+    /// Protocol control byte
+    pub pcb: u8,
+
+    /// Block type
+    pub kind: BlockKind,
+
+    /// Send sequence number N(S) of an I-block or the expected sequence
+    /// number N(R) of an R-block, 0 for an S-block
+    pub seq: u8,
+
+    /// More-data bit of an I-block
+    pub more: bool,
+
+    /// Information field
+    pub inf: &'b [u8],
+
+    /// Error detection code matches
+    pub edc_ok: bool,
+}
+
+/// Decode the block type from the protocol control byte
+pub fn block_kind(pcb: u8) -> BlockKind {
+    if pcb & 0x80 == 0 {
+        BlockKind::I
+    } else if pcb & 0x40 == 0 {
+        BlockKind::R
+    } else {
+        BlockKind::S
+    }
+}
+
+/// Check the more-data bit of an I-block protocol control byte
+pub fn is_chaining(pcb: u8) -> bool {
+    block_kind(pcb) == BlockKind::I && pcb & 0x20 != 0
+}
+
+/// I-block protocol control byte with the send sequence number `seq`
+pub fn iblock_pcb(seq: u8, more: bool) -> u8 {
+    let mut pcb = (seq & 1) << 6;
+    if more {
+        pcb |= 0x20;
+    }
+
+    pcb
+}
+
+/// R-block protocol control byte with the expected sequence number
+/// `seq` and the error code `err`: 0 error free, 1 EDC or parity error,
+/// 2 other errors
+pub fn rblock_pcb(seq: u8, err: u8) -> u8 {
+    0x80 | (seq & 1) << 4 | (err & 3)
+}
+
+/// S-block protocol control byte
+pub fn sblock_pcb(req: SBlockRequest, dir: SBlockDir) -> u8 {
+    match dir {
+        SBlockDir::Request => 0xc0 | req.code(),
+        SBlockDir::Response => 0xe0 | req.code(),
+    }
+}
+
+/// Longitudinal redundancy check, XOR of all bytes
+pub fn lrc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |c, b| c ^ b)
+}
+
+/// CRC-16 CCITT
+pub fn crc16(data: &[u8], little_endian: bool) -> [u8; 2] {
+    let mut c = 0xffffu16;
+
+    for it in data {
+        c ^= u16::from(*it);
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                (c >> 1) ^ 0x8408
+            } else {
+                c >> 1
+            };
+        }
+    }
+
+    if little_endian {
+        c.to_le_bytes()
+    } else {
+        c.to_be_bytes()
+    }
+}
+
+/// Append the error detection code of the first `n` bytes of `buf`,
+/// returns the block length
+pub fn append_edc(buf: &mut [u8], n: usize, algo: ChkAlgo) -> usize {
+    match algo {
+        ChkAlgo::Lrc => buf[n] = lrc8(&buf[..n]),
+        ChkAlgo::Crc { little_endian } => {
+            let crc = crc16(&buf[..n], little_endian);
+            buf[n..n + 2].copy_from_slice(&crc);
+        }
+    }
+
+    n + algo.edc_len()
+}
+
+/// Encode a block into `buf`, returns the block length. `None` if the
+/// information field is longer than 254 bytes or `buf` is too small.
+pub fn encode(buf: &mut [u8], nad: u8, pcb: u8, inf: &[u8], algo: ChkAlgo) -> Option<usize> {
+    let n = HEADER_LEN + inf.len();
+    if inf.len() > INF_MAX || buf.len() < n + algo.edc_len() {
+        return None;
+    }
+
+    buf[0] = nad;
+    buf[1] = pcb;
+    buf[2] = inf.len() as u8;
+    buf[HEADER_LEN..n].copy_from_slice(inf);
+
+    Some(append_edc(buf, n, algo))
+}
+
+/// Encode an I-block into `buf`, returns the block length
+pub fn encode_iblock(
+    buf: &mut [u8],
+    nad: u8,
+    seq: u8,
+    more: bool,
+    inf: &[u8],
+    algo: ChkAlgo,
+) -> Option<usize> {
+    encode(buf, nad, iblock_pcb(seq, more), inf, algo)
+}
+
+/// Encode an R-block into `buf`, returns the block length
+pub fn encode_rblock(buf: &mut [u8], nad: u8, seq: u8, err: u8, algo: ChkAlgo) -> Option<usize> {
+    encode(buf, nad, rblock_pcb(seq, err), &[], algo)
+}
+
+/// Encode an S-block into `buf`, returns the block length
+pub fn encode_sblock(
+    buf: &mut [u8],
+    nad: u8,
+    req: SBlockRequest,
+    dir: SBlockDir,
+    inf: &[u8],
+    algo: ChkAlgo,
+) -> Option<usize> {
+    encode(buf, nad, sblock_pcb(req, dir), inf, algo)
+}
+
+/// Decode a raw block. `None` if the length does not match the LEN byte.
+pub fn decode(frame: &[u8], algo: ChkAlgo) -> Option<BlockView<'_>> {
+    let (&[nad, pcb, len], rest) = frame.split_first_chunk::<HEADER_LEN>()?;
+    let n = HEADER_LEN + usize::from(len);
+    if rest.len() != usize::from(len) + algo.edc_len() {
+        return None;
+    }
+
+    let kind = block_kind(pcb);
+    let edc_ok = match algo {
+        ChkAlgo::Lrc => lrc8(&frame[..n]) == frame[n],
+        ChkAlgo::Crc { little_endian } => crc16(&frame[..n], little_endian) == frame[n..],
+    };
+
+    Some(BlockView {
+        nad,
+        pcb,
+        kind,
+        seq: match kind {
+            BlockKind::I => pcb >> 6 & 1,
+            BlockKind::R => pcb >> 4 & 1,
+            BlockKind::S => 0,
+        },
+        more: is_chaining(pcb),
+        inf: &frame[HEADER_LEN..n],
+        edc_ok,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGOS: [ChkAlgo; 3] = [
+        ChkAlgo::Lrc,
+        ChkAlgo::Crc {
+            little_endian: false,
+        },
+        ChkAlgo::Crc {
+            little_endian: true,
+        },
+    ];
+
+    #[test]
+    fn test_display() {
+        use std::string::ToString;
+
+        assert_eq!(BlockKind::I.to_string(), "I-block");
+        assert_eq!(BlockKind::R.to_string(), "R-block");
+        assert_eq!(BlockKind::S.to_string(), "S-block");
+        assert_eq!(SBlockRequest::VppError.to_string(), "VPP error");
+        assert_eq!(SBlockRequest::Other(0x1f).to_string(), "0x1f");
+        assert_eq!(SBlock::from_pcb(0xc1).to_string(), "S(IFS request)");
+        assert_eq!(SBlock::from_pcb(0xe3).to_string(), "S(WTX response)");
+        assert_eq!(SBlock::from_pcb(0xc5).to_string(), "S(RESET request)");
+        assert_eq!(SBlock::from_pcb(0xe0).to_string(), "S(RESYNCH response)");
+    }
+
+    #[test]
+    fn test_codes() {
+        for (pcb, code) in [(0x00, 0x00), (0x60, 0x00), (0x92, 0x80), (0xe5, 0xc0)] {
+            assert_eq!(block_kind(pcb).code(), code);
+        }
+        for pcb in 0xc0..=0xff {
+            assert_eq!(SBlock::from_pcb(pcb).pcb(), pcb);
+        }
+    }
+
+    #[test]
+    fn test_crc16_check() {
+        assert_eq!(crc16(b"123456789", false), [0x6f, 0x91]);
+        assert_eq!(crc16(b"123456789", true), [0x91, 0x6f]);
+    }
+
+    #[test]
+    fn test_encode_known() {
+        let mut buf = [0u8; 16];
+
+        let n = encode_iblock(
+            &mut buf,
+            0x51,
+            0,
+            false,
+            &[0x80, 0xca, 0x9f, 0x7f],
+            ChkAlgo::Lrc,
+        );
+        assert_eq!(
+            buf[..n.unwrap()],
+            [0x51, 0x00, 0x04, 0x80, 0xca, 0x9f, 0x7f, 0xff]
+        );
+
+        let n = encode_rblock(&mut buf, 0x51, 1, 0, ChkAlgo::Lrc);
+        assert_eq!(buf[..n.unwrap()], [0x51, 0x90, 0x00, 0xc1]);
+
+        let n = encode_sblock(
+            &mut buf,
+            0x51,
+            SBlockRequest::Reset,
+            SBlockDir::Request,
+            &[],
+            ChkAlgo::Lrc,
+        );
+        assert_eq!(buf[..n.unwrap()], [0x51, 0xc5, 0x00, 0x94]);
+    }
+
+    #[test]
+    fn test_encode_too_long() {
+        let mut buf = [0u8; 260];
+
+        assert_eq!(encode(&mut buf, 0x51, 0, &[0; 255], ChkAlgo::Lrc), None);
+        assert_eq!(encode(&mut buf[..4], 0x51, 0, &[0; 1], ChkAlgo::Lrc), None);
+        assert_eq!(
+            encode(&mut buf[..5], 0x51, 0, &[0; 1], ChkAlgo::Lrc),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_decode_bad_len() {
+        let block = [0x15, 0x00, 0x02, 0x90, 0x00, 0x87];
+
+        assert!(decode(&block, ChkAlgo::Lrc).is_some());
+        assert_eq!(decode(&block[..5], ChkAlgo::Lrc), None);
+        assert_eq!(decode(&block[..2], ChkAlgo::Lrc), None);
+        assert_eq!(
+            decode(
+                &block,
+                ChkAlgo::Crc {
+                    little_endian: false
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_bad_edc() {
+        let view = decode(&[0x15, 0x00, 0x02, 0x90, 0x00, 0x00], ChkAlgo::Lrc).unwrap();
+
+        assert_eq!(view.inf, [0x90, 0x00]);
+        assert!(!view.edc_ok);
+    }
+
+    #[test]
+    fn test_round_trip_iblock() {
+        let mut buf = [0u8; 260];
+        let data: [u8; INF_MAX] = core::array::from_fn(|i| i as u8);
+
+        for algo in ALGOS {
+            for len in 0..=INF_MAX {
+                for (seq, more) in [(0, false), (1, false), (0, true), (1, true)] {
+                    let n = encode_iblock(&mut buf, 0x51, seq, more, &data[..len], algo).unwrap();
+                    let view = decode(&buf[..n], algo).unwrap();
+
+                    assert_eq!(view.nad, 0x51);
+                    assert_eq!(view.kind, BlockKind::I);
+                    assert_eq!(view.seq, seq);
+                    assert_eq!(view.more, more);
+                    assert_eq!(view.inf, &data[..len]);
+                    assert!(view.edc_ok);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_rblock() {
+        let mut buf = [0u8; 8];
+
+        for algo in ALGOS {
+            for seq in 0..2 {
+                for err in 0..3 {
+                    let n = encode_rblock(&mut buf, 0x51, seq, err, algo).unwrap();
+                    let view = decode(&buf[..n], algo).unwrap();
+
+                    assert_eq!(view.kind, BlockKind::R);
+                    assert_eq!(view.pcb, rblock_pcb(seq, err));
+                    assert_eq!(view.seq, seq);
+                    assert!(!view.more);
+                    assert!(view.inf.is_empty());
+                    assert!(view.edc_ok);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_sblock() {
+        let mut buf = [0u8; 8];
+        let requests = [
+            SBlockRequest::Resynch,
+            SBlockRequest::Ifs,
+            SBlockRequest::Abort,
+            SBlockRequest::Wtx,
+            SBlockRequest::VppError,
+            SBlockRequest::Reset,
+        ];
+
+        for algo in ALGOS {
+            for req in requests {
+                for dir in [SBlockDir::Request, SBlockDir::Response] {
+                    let n = encode_sblock(&mut buf, 0x15, req, dir, &[0x20], algo).unwrap();
+                    let view = decode(&buf[..n], algo).unwrap();
+
+                    assert_eq!(view.kind, BlockKind::S);
+                    assert_eq!(SBlockRequest::from_pcb(view.pcb), req);
+                    assert_eq!(view.pcb & 0x20 != 0, dir == SBlockDir::Response);
+                    assert_eq!(view.inf, [0x20]);
+                    assert!(view.edc_ok);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Block by block response reception
+//!
+
+use crate::{Error, Transmission};
+use core::fmt;
+
+/// Iterator over the response APDU received block by block.
+///
+/// Each item is the payload of one received I-block. The blocks are
+/// exchanged on demand and placed one after another in the response
+/// buffer, so the yielded slices stay valid after the next call.
+pub struct ResponseBlocks<'t, 'b, T, E> {
+    t: &'t mut Transmission<T, E>,
+    capdu: &'b [u8],
+    buf: &'b mut [u8],
+    started: bool,
+    done: bool,
+}
+
+impl<'t, 'b, T, E: Clone> ResponseBlocks<'t, 'b, T, E> {
+    fn step(&mut self) -> Result<Option<&'b [u8]>, Error<E>> {
+        if !self.started {
+            self.t.try_init()?;
+            self.t.t1.transmit_begin(self.capdu.len(), self.buf.len());
+            self.started = true;
+        }
+
+        let ifc = self.t.interface.as_ref();
+        let read = self.t.block_read_cb()?;
+        let write = self.t.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        let read = |b: &mut [u8]| read(ifc, b);
+        let write = |b: &[u8]| write(ifc, b);
+
+        let ret = loop {
+            match self.t.t1.process_step(&read, &write, self.capdu, self.buf) {
+                Ok(true) => {
+                    let n = self.t.t1.take_received();
+                    if n > 0 {
+                        break Ok(Some(n));
+                    }
+                }
+                Ok(false) => break self.t.t1.process_end().map(|_| None),
+                Err(e) => break Err(e),
+            }
+        };
+
+        let Some(n) = self.t.track(ret)? else {
+            return Ok(None);
+        };
+        let buf = core::mem::take(&mut self.buf);
+        let (block, rest) = buf.split_at_mut(n);
+        self.buf = rest;
+
+        Ok(Some(block))
+    }
+}
+
+impl<'t, 'b, T, E: Clone> Iterator for ResponseBlocks<'t, 'b, T, E> {
+    type Item = Result<&'b [u8], Error<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.step() {
+            Ok(Some(block)) => Some(Ok(block)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T, E: Clone> Transmission<T, E> {
+    /// Transmit command APDU and write the response hex encoded to
+    /// `writer` as it is received, returning the response length. Data
+    /// of the received blocks is written even if the exchange fails later.
+    pub fn transmit_to_hex_writer(
+        &mut self,
+        capdu: &[u8],
+        writer: &mut impl fmt::Write,
+    ) -> Result<usize, Error<E>> {
+        self.try_init()?;
+
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        let read = |b: &mut [u8]| read(ifc, b);
+        let write = |b: &[u8]| write(ifc, b);
+
+        let mut total = 0;
+        self.t1.transmit_begin_discard(capdu.len());
+        let ret = loop {
+            match self.t1.process_step(&read, &write, capdu, &mut []) {
+                Ok(true) => {
+                    let n = self.t1.take_received();
+                    if n > 0 {
+                        for b in self.t1.block_data() {
+                            write!(writer, "{b:02x}").map_err(|_| Error::HexWrite)?;
+                        }
+                        total += n;
+                    }
+                }
+                Ok(false) => break self.t1.process_end(),
+                Err(e) => break Err(e),
+            }
+        };
+        self.track(ret)?;
+
+        Ok(total)
+    }
+
+    /// Transmit command APDU and receive the response block by block
+    /// while iterating
+    pub fn response_blocks<'t, 'b>(
+        &'t mut self,
+        capdu: &'b [u8],
+        rapdu: &'b mut [u8],
+    ) -> ResponseBlocks<'t, 'b, T, E> {
+        ResponseBlocks {
+            t: self,
+            capdu,
+            buf: rapdu,
+            started: false,
+            done: false,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! APDU command chaining
+//!
+
+use crate::apdu::DATA_MAX;
+use crate::{Apdu, Error, Response, Transmission};
+
+/// Status byte SW1 of a warning with the non-volatile memory unchanged
+const SW1_WARNING: u8 = 0x63;
+
+impl<T, E: Clone> Transmission<T, E> {
+    /// Transmit command APDU with data longer than 255 bytes using ISO
+    /// command chaining. The data is sent in segments of at most 255 bytes,
+    /// all but the last one with CLA | 0x10 and no Le. Other CLA bits,
+    /// such as the logical channel and secure messaging, are kept.
+    ///
+    /// Each intermediate segment must be answered with 9000, or 63XX if
+    /// chain warnings are enabled. The last segment response is returned.
+    pub fn transmit_chained<'r>(
+        &mut self,
+        apdu: &Apdu,
+        rapdu: &'r mut [u8],
+    ) -> Result<Response<'r>, Error<E>> {
+        let mut off = 0;
+
+        while apdu.data_len() - off > DATA_MAX {
+            let sw = self.transmit_apdu(&apdu.chain_part(off), rapdu)?.sw();
+            if !self.chain_sw_ok(sw) {
+                return Err(Error::ChainRejected(sw));
+            }
+            off += DATA_MAX;
+        }
+
+        self.transmit_apdu(&apdu.chain_last(off), rapdu)
+    }
+
+    fn chain_sw_ok(&self, sw: u16) -> bool {
+        sw == 0x9000 || (self.chain_warnings && sw >> 8 == u16::from(SW1_WARNING))
+    }
+}
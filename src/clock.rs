@@ -1,26 +1,185 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
 type SleepCb = fn(u32);
+type NowCb = fn() -> u32;
 
+/// Timeout clock. With a monotonic clock callback the timeout is checked
+/// against real time since creation. Otherwise only the time spent in
+/// `sleep()` is counted, so time spent in the read callback is not.
 pub struct Clock {
     timeout: u32,
     time: u32,
-    sleep_cb: SleepCb,
+    start: Option<u32>,
+    sleep_cb: Option<SleepCb>,
+    now_cb: Option<NowCb>,
+    sim: Option<&'static SimulatedTime>,
+}
+
+/// Simulated monotonic time in milliseconds for deterministic timeout
+/// tests. The protocol sleeps advance it instead of calling the sleeping
+/// callback.
+#[derive(Debug, Default)]
+pub struct SimulatedTime(AtomicU32);
+
+impl SimulatedTime {
+    /// Simulated time starting at zero
+    pub const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    /// Advance the simulated time, e.g. from a read callback emulating a
+    /// slow card
+    pub fn advance(&self, ms: u32) {
+        self.0.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// Current simulated time
+    pub fn now(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 impl Clock {
-    pub fn new(timeout: u32, sleep_cb: SleepCb) -> Self {
+    pub fn new(timeout: u32, sleep_cb: Option<SleepCb>, now_cb: Option<NowCb>) -> Self {
         Self {
             timeout,
             time: 0,
+            start: now_cb.map(|now| now()),
             sleep_cb,
+            now_cb,
+            sim: None,
+        }
+    }
+
+    /// Clock advancing the simulated time instead of sleeping
+    pub fn new_simulated(timeout: u32, sim: &'static SimulatedTime) -> Self {
+        Self {
+            timeout,
+            time: 0,
+            start: Some(sim.now()),
+            sleep_cb: None,
+            now_cb: None,
+            sim: Some(sim),
         }
     }
 
     pub fn sleep(&mut self, time: u32) {
-        (self.sleep_cb)(time);
-        self.time += time;
+        if let Some(sim) = self.sim {
+            sim.advance(time);
+        } else if let Some(sleep) = self.sleep_cb {
+            sleep(time);
+        } else if let Some(now) = self.now_cb {
+            // Busy-wait until the monotonic clock advances
+            let start = now();
+            while now().wrapping_sub(start) < time {}
+        }
+        self.time = self.time.saturating_add(time);
+    }
+
+    pub fn elapsed(&self) -> u32 {
+        match (self.sim, self.now_cb, self.start) {
+            (Some(sim), _, Some(start)) => sim.now().wrapping_sub(start),
+            (None, Some(now), Some(start)) => now().wrapping_sub(start),
+            _ => self.time,
+        }
     }
 
     pub fn timeout(&self) -> bool {
-        self.time > self.timeout
+        self.elapsed() > self.timeout
+    }
+
+    /// Time left until the timeout, zero once expired
+    pub fn remaining(&self) -> u32 {
+        self.timeout.saturating_sub(self.elapsed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_timeout() {
+        static SIM: SimulatedTime = SimulatedTime::new();
+        let clock = Clock::new_simulated(300, &SIM);
+        assert!(!clock.timeout());
+
+        SIM.advance(300);
+        assert!(!clock.timeout());
+
+        SIM.advance(1);
+        assert!(clock.timeout());
+    }
+
+    #[test]
+    fn test_simulated_sleep() {
+        static SIM: SimulatedTime = SimulatedTime::new();
+        SIM.advance(100);
+        let mut clock = Clock::new_simulated(10, &SIM);
+
+        clock.sleep(6);
+        assert_eq!(SIM.now(), 106);
+        assert!(!clock.timeout());
+
+        clock.sleep(6);
+        assert!(clock.timeout());
+    }
+
+    #[test]
+    fn test_remaining() {
+        static SIM: SimulatedTime = SimulatedTime::new();
+        let clock = Clock::new_simulated(10, &SIM);
+        assert_eq!(clock.remaining(), 10);
+
+        SIM.advance(4);
+        assert_eq!(clock.remaining(), 6);
+
+        SIM.advance(20);
+        assert_eq!(clock.remaining(), 0);
+    }
+
+    #[test]
+    fn test_real_time_timeout() {
+        static NOW: AtomicU32 = AtomicU32::new(u32::MAX - 150);
+
+        fn now() -> u32 {
+            NOW.load(Ordering::Relaxed)
+        }
+
+        fn sleep(ms: u32) {
+            NOW.fetch_add(ms, Ordering::Relaxed);
+        }
+
+        /// Read callback waiting 100 ms on each call
+        fn slow_read() {
+            NOW.fetch_add(100, Ordering::Relaxed);
+        }
+
+        let mut clock = Clock::new(300, Some(sleep), Some(now));
+
+        let mut polls = 0;
+        while !clock.timeout() {
+            clock.sleep(2);
+            slow_read();
+            polls += 1;
+        }
+
+        assert_eq!(polls, 3);
+        assert_eq!(clock.elapsed(), 306);
+    }
+
+    #[test]
+    fn test_sleep_time_timeout() {
+        // Without a clock the time spent reading is not seen at all
+        let mut clock = Clock::new(300, Some(|_| ()), None);
+
+        let mut polls = 0;
+        while !clock.timeout() {
+            clock.sleep(2);
+            polls += 1;
+        }
+
+        assert_eq!(polls, 151);
+        assert_eq!(clock.elapsed(), 302);
     }
 }
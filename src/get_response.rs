@@ -0,0 +1,123 @@
+//! Automatic GET RESPONSE chaining
+//!
+
+use crate::{Apdu, ApduError, Error, Response, Transmission};
+
+/// GET RESPONSE command header, Le byte is taken from SW2
+const GET_RESPONSE: [u8; 4] = [0x00, 0xc0, 0x00, 0x00];
+
+/// Status byte SW1 indicating more response bytes are available
+const SW1_MORE_DATA: u8 = 0x61;
+
+/// Iterator issuing GET RESPONSE commands while the card reports more
+/// response bytes available with SW1 = 0x61.
+///
+/// Each item is a full response APDU including its status word. Commands
+/// and responses are placed one after another in the work buffer, so the
+/// yielded slices stay valid after the next call.
+pub struct GetResponseIter<'t, 'b, T, E> {
+    t: &'t mut Transmission<T, E>,
+    buf: &'b mut [u8],
+    cla: u8,
+    le: Option<u8>,
+}
+
+impl<'t, 'b, T, E: Clone> Iterator for GetResponseIter<'t, 'b, T, E> {
+    type Item = Result<&'b [u8], Error<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let le = self.le.take()?;
+
+        let buf = core::mem::take(&mut self.buf);
+        if buf.len() < GET_RESPONSE.len() + 1 {
+            return Some(Err(Error::WorkBufTooSmall(buf.len())));
+        }
+
+        let (capdu, rapdu) = buf.split_at_mut(GET_RESPONSE.len() + 1);
+        capdu[..GET_RESPONSE.len()].copy_from_slice(&GET_RESPONSE);
+        capdu[0] = self.cla;
+        capdu[GET_RESPONSE.len()] = le;
+
+        let n = match self.t.transmit_into(capdu, rapdu) {
+            Ok(n) => n,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let (resp, rest) = rapdu.split_at_mut(n);
+        self.buf = rest;
+        self.le = more_data(resp);
+
+        Some(Ok(resp))
+    }
+}
+
+/// Le for the next GET RESPONSE if the response status word is 61XX
+fn more_data(rapdu: &[u8]) -> Option<u8> {
+    match rapdu {
+        [.., SW1_MORE_DATA, sw2] => Some(*sw2),
+        _ => None,
+    }
+}
+
+/// GET RESPONSE class byte keeping the logical channel of `cla`
+fn get_response_cla(cla: u8) -> u8 {
+    if cla & 0x40 != 0 {
+        0x40 | (cla & 0x0f)
+    } else {
+        cla & 0x03
+    }
+}
+
+impl<T, E: Clone> Transmission<T, E> {
+    /// Transmit command APDU and issue GET RESPONSE commands while the
+    /// card answers with SW1 = 0x61, appending the data to `rapdu`.
+    ///
+    /// Stops with the last 61XX status word kept when `rapdu` has no room
+    /// for the announced bytes, Le = 0x00 announcing 256 bytes.
+    pub fn transmit_apdu_auto<'r>(
+        &mut self,
+        apdu: &Apdu,
+        rapdu: &'r mut [u8],
+    ) -> Result<Response<'r>, Error<E>> {
+        let mut len = self.transmit_apdu(apdu, rapdu)?.data().len() + 2;
+
+        while let Some(le) = more_data(&rapdu[..len]) {
+            let data_len = len - 2;
+            let want = if le == 0 { 256 } else { usize::from(le) };
+            if rapdu.len() - data_len < want + 2 {
+                break;
+            }
+
+            let mut capdu = [0; GET_RESPONSE.len() + 1];
+            capdu[..GET_RESPONSE.len()].copy_from_slice(&GET_RESPONSE);
+            capdu[0] = get_response_cla(apdu.cla());
+            capdu[GET_RESPONSE.len()] = le;
+
+            let n = self.transmit_into(&capdu, &mut rapdu[data_len..])?;
+            if n < 2 {
+                return Err(Error::Apdu(ApduError::RespTooShort(n)));
+            }
+            len = data_len + n;
+        }
+
+        let rapdu: &'r [u8] = rapdu;
+        Response::parse(&rapdu[..len]).map_err(Error::Apdu)
+    }
+
+    /// Fetch the remaining response bytes while the card answers with
+    /// SW1 = 0x61. Nothing is sent if `initial_rapdu` has another status.
+    /// GET RESPONSE is sent on the logical channel of `initial_capdu`.
+    pub fn auto_get_response<'t, 'b>(
+        &'t mut self,
+        initial_capdu: &[u8],
+        initial_rapdu: &[u8],
+        work_buf: &'b mut [u8],
+    ) -> GetResponseIter<'t, 'b, T, E> {
+        GetResponseIter {
+            t: self,
+            buf: work_buf,
+            cla: get_response_cla(initial_capdu.first().copied().unwrap_or(0)),
+            le: more_data(initial_rapdu),
+        }
+    }
+}
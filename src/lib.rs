@@ -73,29 +73,79 @@
 //!struct Interface{}
 //!
 //!// Interface errors
-//!#[derive(Debug)]
+//!#[derive(Debug, Clone)]
 //!enum Error {}
 //!
 //! ```
 
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(clippy::needless_doctest_main)]
 
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod apdu;
+pub mod atr;
+pub mod block;
+mod blocks;
+mod chaining;
 mod clock;
+mod get_response;
 mod proto;
+mod select;
+mod status;
+pub mod t1;
+
+use clock::Clock;
+use core::convert::Infallible;
+use core::fmt;
+use core::marker::PhantomData;
+use core::sync::atomic::AtomicBool;
+use proto::{T1Proto, CHANNELS_MAX};
+
+pub use apdu::{Apdu, ApduError, Response};
+pub use block::{block_kind, is_chaining, BlockKind, ChkAlgo};
+pub use blocks::ResponseBlocks;
+pub use clock::SimulatedTime;
+pub use get_response::GetResponseIter;
+pub use proto::{
+    Direction, Error as T1Error, IterEvent, PpsResult, ProtocolState, RetryCause, Stats, T1Iter,
+};
+pub use status::StatusWord;
 
-use proto::T1Proto;
+#[cfg(feature = "alloc")]
+pub use proto::dump_block;
 
 type InitCb<T, E> = fn() -> Result<Option<T>, E>;
 type ReleaseCb<T, E> = fn(Option<&T>) -> Result<Option<T>, E>;
 type ResetCb<T, E> = fn(Option<&T>) -> Result<(), E>;
 type ReadCb<T, E> = fn(Option<&T>, &mut [u8]) -> Result<usize, E>;
 type WriteCb<T, E> = fn(Option<&T>, &[u8]) -> Result<usize, E>;
+type NowCb = fn() -> u32;
+type AtrFilter = fn(&[u8]) -> bool;
+type GpioResetCb = fn(bool);
+type TraceCb = fn(Direction, &[u8]);
+
+/// Default RST pin assertion time in milliseconds
+const RESET_ASSERT_MS: u32 = 1;
+
+/// Default delay after RST pin de-assertion in milliseconds
+const RESET_DEASSERT_MS: u32 = 10;
+
+/// Delay between the Answer To Reset attempts in milliseconds
+const ATR_RETRY_MS: u32 = 10;
+
+/// Status byte SW1 announcing wrong Le with the exact length in SW2
+const SW1_WRONG_LE: u8 = 0x6c;
 
 /// Main ISO7816 Transmission API structure
 #[derive(Default)]
-pub struct Transmission<'a, T, E> {
+pub struct Transmission<T, E> {
     /// ISO/IEC 7816 T=1 transmission protocol context
-    t1: T1Proto<'a, E>,
+    t1: T1Proto<E>,
 
     /// Smart Card communication interface context
     interface: Option<T>,
@@ -109,101 +159,416 @@ pub struct Transmission<'a, T, E> {
     /// Connection interface reset callback
     reset_cb: Option<ResetCb<T, E>>,
 
+    /// RST pin control callback, asserts RST when called with `true`
+    gpio_reset_cb: Option<GpioResetCb>,
+
+    /// RST pin assertion time
+    reset_assert_ms: Option<u32>,
+
+    /// Delay after RST pin de-assertion
+    reset_deassert_ms: Option<u32>,
+
     /// Connection interface read callback
     read_cb: Option<ReadCb<T, E>>,
 
+    /// Connection interface whole block read callback
+    read_block_cb: Option<ReadCb<T, E>>,
+
     /// Connection interface write callback
     write_cb: Option<WriteCb<T, E>>,
 
     /// Timer sleeping callback
     sleep_cb: Option<fn(u32)>,
 
+    /// Monotonic clock callback
+    now_cb: Option<NowCb>,
+
+    /// Simulated time replacing the sleeping and clock callbacks
+    sim_time: Option<&'static SimulatedTime>,
+
+    /// Host abort request flag, shared with the requesting context
+    abort_flag: Option<&'static AtomicBool>,
+
+    /// Cooperative scheduler yielding callback
+    yield_cb: Option<fn()>,
+
+    /// Card response waiting progress callback
+    wait_progress_cb: Option<fn(u32)>,
+
+    /// Card session start callback
+    on_session_start: Option<fn()>,
+
+    /// Card session end callback
+    on_session_end: Option<fn()>,
+
+    /// Protocol state transition tracing callback
+    #[cfg(feature = "trace")]
+    state_cb: Option<fn(&str)>,
+
+    /// Raw block tracing callback
+    trace_cb: Option<TraceCb>,
+
+    /// NAD bytes of the additional logical channels
+    channel_nads: [Option<(u8, u8)>; CHANNELS_MAX],
+
     /// NAD byte for Smart Card
     card_nad: Option<u8>,
 
     /// NAD byte for device
     dev_nad: Option<u8>,
 
+    /// Timeout for receiving the Smart Card NAD byte
+    nad_timeout: Option<u32>,
+
+    /// Upper bound of a whole exchange duration
+    total_timeout: Option<u32>,
+
+    /// Maximum number of noise bytes discarded before the NAD byte
+    noise_max: Option<usize>,
+
+    /// Character waiting time within a received block
+    cwt: Option<u32>,
+
+    /// Accepted Answer To Reset matcher
+    atr_filter: Option<AtrFilter>,
+
+    /// Maximum response APDU length
+    recv_max: Option<usize>,
+
+    /// Block buffer size
+    buf_size: Option<usize>,
+
+    /// Maximum accepted waiting time extension multiplier
+    wtx_max_value: Option<u8>,
+
+    /// Maximum number of consecutive waiting time extensions
+    wtx_max_rounds: Option<i32>,
+
+    /// Block error detection code
+    chk_algo: ChkAlgo,
+
     /// Transmission protocol context is initialized
     inited: bool,
 
+    /// Connection interface is released
+    released: bool,
+
     /// Enable Software reset using connection interface
     soft_reset: bool,
+
+    /// Accept blocks with reserved PCB bits set or unknown codes
+    lenient_pcb: bool,
+
+    /// Accept blocks with any source NAD
+    any_card_nad: bool,
+
+    /// Re-send command APDU with the Le announced by 6CXX status
+    wrong_le_retry: bool,
+
+    /// Accept 63XX warning status for the intermediate chained commands
+    chain_warnings: bool,
+
+    /// Fetch the remaining response with GET RESPONSE in `select_aid()`
+    auto_get_response: bool,
+
+    /// Fail the reset if no cold reset callback is set
+    cold_reset_required: bool,
+
+    /// Keep the connection interface open across release and init
+    keep_interface: bool,
+
+    /// Protocol error of the last failed operation
+    last_error: Option<proto::Error<E>>,
 }
 
-impl<'a, T, E> Transmission<'a, T, E> {
+impl<T, E> Transmission<T, E> {
     /// Initialize Transmission context
     pub fn init(&mut self) -> Result<(), Error<E>> {
         if self.inited {
             return Err(Error::AlreadyInited);
         }
 
-        self.interface = match self.init_cb {
-            Some(cb) => cb().map_err(Error::InitCbErr)?,
-            None => None,
-        };
+        if !self.keep_interface || self.interface.is_none() {
+            self.interface = match self.init_cb {
+                Some(cb) => cb().map_err(Error::InitCbErr)?,
+                None => None,
+            };
+        }
 
         let card_nad = self.card_nad.ok_or(Error::NadNotSet)?;
         let dev_nad = self.dev_nad.ok_or(Error::NadNotSet)?;
         self.t1.set_nad(card_nad, dev_nad);
-        self.t1.set_sleep_cb(self.sleep_cb.ok_or(Error::NoSleepCb)?);
+        for (ch, nad) in self.channel_nads.iter().enumerate().skip(1) {
+            if let Some((card_nad, dev_nad)) = *nad {
+                self.t1.set_channel_nad(ch as u8, card_nad, dev_nad);
+            }
+        }
+        self.t1.set_nad_timeout(self.nad_timeout);
+        self.t1.set_total_timeout(self.total_timeout);
+        self.t1.set_noise_max(self.noise_max);
+        self.t1.set_cwt(self.cwt);
+        self.t1.set_chk_algo(self.chk_algo);
+        if let Some(max) = self.recv_max {
+            if max < 2 {
+                return Err(Error::RecvMaxTooSmall(max));
+            }
+            self.t1.set_recv_max(max);
+        }
+        if let Some(size) = self.buf_size {
+            self.t1.set_buf_size(size);
+        }
+        if let Some(v) = self.wtx_max_value {
+            self.t1.set_wtx_max_value(v);
+        }
+        if let Some(rounds) = self.wtx_max_rounds {
+            self.t1.set_wtx_max_rounds(rounds);
+        }
+        if self.sleep_cb.is_none() && self.now_cb.is_none() && self.sim_time.is_none() {
+            return Err(Error::NoSleepCb);
+        }
+        self.t1.set_sleep_cb(self.sleep_cb);
+        self.t1.set_now_cb(self.now_cb);
+        self.t1.set_simulated_time(self.sim_time);
+        self.t1.set_abort_flag(self.abort_flag);
+        if let Some(cb) = self.yield_cb {
+            self.t1.set_yield_cb(cb);
+        }
+        self.t1.set_wait_progress_cb(self.wait_progress_cb);
+        #[cfg(feature = "trace")]
+        self.t1.set_state_cb(self.state_cb);
+        self.t1.set_trace_cb(self.trace_cb);
         self.t1.set_soft_reset(self.soft_reset);
+        self.t1.set_lenient(self.lenient_pcb);
+        self.t1.set_any_card_nad(self.any_card_nad);
+        self.t1.set_block_read(self.read_block_cb.is_some());
         self.inited = true;
+        self.released = false;
+
+        if let Some(cb) = self.on_session_start {
+            cb();
+        }
 
         Ok(())
     }
 
-    /// Reset Transmission protocol states
-    pub fn reset(&mut self) -> Result<(), Error<E>> {
+    /// Set the block waiting time from the waiting time integer BWI of
+    /// the last Answer To Reset. The default is used if the ATR has none.
+    pub fn apply_atr_timings(&mut self) {
+        self.t1.apply_atr_timings();
+    }
+
+    /// Get block waiting time in milliseconds
+    pub fn bwt(&self) -> u32 {
+        self.t1.bwt()
+    }
+
+    /// Run only the IFS negotiation with the card and get the negotiated
+    /// IFS value
+    pub fn sync_ifs(&mut self) -> Result<u8, Error<E>> {
         self.try_init()?;
 
-        // Cold reset
-        if let Some(cb) = self.reset_cb {
-            cb(self.interface.as_ref()).map_err(Error::ResetCbErr)?
-        }
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        Ok(self.t1.sync_ifs(|b| read(ifc, b), |b| write(ifc, b))?)
+    }
+
+    /// Resynchronize with the card by S(RESYNCH) exchange without a reset.
+    /// Both sequence numbers and the information field sizes are set to
+    /// their initial values, the ATR is kept.
+    pub fn resync(&mut self) -> Result<(), Error<E>> {
+        self.try_init()?;
 
-        // Soft reset
         let ifc = self.interface.as_ref();
-        let read = self.read_cb.as_ref().ok_or(Error::NoReadCb)?;
+        let read = self.block_read_cb()?;
         let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
-        self.t1
-            .reset(|b| read(ifc, b), |b| write(ifc, b))
-            .map_err(Error::T1)
+        Ok(self.t1.resync(|b| read(ifc, b), |b| write(ifc, b))?)
     }
 
-    /// Get Answer To Reset (ATR)
-    pub fn atr(&mut self) -> Result<&[u8], Error<E>> {
+    /// Negotiate the device information field size IFSD by S(IFS)
+    /// exchange, `ifsd` from 1 to 254 limited by the block buffer size.
+    /// The card may accept a smaller value, which is returned.
+    pub fn negotiate_ifsd(&mut self, ifsd: u8) -> Result<u8, Error<E>> {
         self.try_init()?;
 
         let ifc = self.interface.as_ref();
-        let read = self.read_cb.as_ref().ok_or(Error::NoReadCb)?;
+        let read = self.block_read_cb()?;
         let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
-        self.t1
-            .atr(|b| read(ifc, b), |b| write(ifc, b))
-            .map_err(Error::T1)
+        Ok(self
+            .t1
+            .negotiate_ifsd(ifsd, |b| read(ifc, b), |b| write(ifc, b))?)
     }
 
-    /// Transmit APDU data and get the response
-    pub fn transmit(&mut self, capdu: &'a [u8], rapdu: &'a mut [u8]) -> Result<&[u8], Error<E>> {
+    /// Negotiate protocol parameters (PPS) with the card after ATR
+    pub fn negotiate_pps(&mut self, pps0: u8, pps1: Option<u8>) -> Result<PpsResult, Error<E>> {
         self.try_init()?;
 
         let ifc = self.interface.as_ref();
         let read = self.read_cb.as_ref().ok_or(Error::NoReadCb)?;
         let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
-        self.t1
-            .transmit(capdu, rapdu, |b| read(ifc, b), |b| write(ifc, b))
-            .map_err(Error::T1)
+        Ok(self
+            .t1
+            .negotiate_pps(pps0, pps1, |b| read(ifc, b), |b| write(ifc, b))?)
+    }
+
+    /// Request the next exchange to be aborted. Its first block sent is
+    /// S(ABORT request) and it ends with `Aborted` once the card responds.
+    /// To abort an exchange from another context while it runs, e.g. an
+    /// interrupt, register a flag with `set_abort_flag()` of the builder.
+    pub fn abort_transmit(&self) {
+        self.t1.request_abort();
+    }
+
+    /// Abort the chained transmission left in progress by a failed
+    /// `transmit()`. Sends S(ABORT request), waits for the card response
+    /// and closes the send and receive windows. Does nothing if no chain
+    /// is in progress.
+    pub fn abort(&mut self) -> Result<(), Error<E>> {
+        self.try_init()?;
+
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        Ok(self.t1.abort(|b| read(ifc, b), |b| write(ifc, b))?)
+    }
+
+    /// Decompose Transmission into the connection interface and callbacks,
+    /// e.g. to hand the interface over to another driver. The interface
+    /// is not released.
+    pub fn into_parts(mut self) -> TransmissionParts<T, E> {
+        self.released = true;
+
+        TransmissionParts {
+            interface: self.interface.take(),
+            init_cb: self.init_cb,
+            release_cb: self.release_cb,
+            reset_cb: self.reset_cb,
+            gpio_reset_cb: self.gpio_reset_cb,
+            read_cb: self.read_cb,
+            read_block_cb: self.read_block_cb,
+            write_cb: self.write_cb,
+            sleep_cb: self.sleep_cb,
+            now_cb: self.now_cb,
+            sim_time: self.sim_time,
+            yield_cb: self.yield_cb,
+        }
+    }
+
+    /// Check if the connection interface is initialized
+    pub fn is_inited(&self) -> bool {
+        self.inited
+    }
+
+    /// Check if the card reset and ATR are pending
+    pub fn needs_reset(&self) -> bool {
+        self.t1.needs_reset()
+    }
+
+    /// Protocol state snapshot left by the last exchange
+    pub fn protocol_state(&self) -> ProtocolState {
+        self.t1.protocol_state()
+    }
+
+    /// Last protocol error recovered during the last exchange, e.g. a bad
+    /// checksum followed by a successful retransmission. Set when the
+    /// exchange succeeds, while `last_error()` is the error of a failed
+    /// operation.
+    pub fn recovered_error(&self) -> Option<&T1Error<E>> {
+        self.t1.recovered_error()
+    }
+
+    /// Block exchange retries left, 3 after a successful operation and 0
+    /// after one failed with exhausted retries
+    pub fn t1_retries_remaining(&self) -> u8 {
+        self.t1.retries_remaining()
+    }
+
+    /// S(WTX request) rounds left before the exchange fails with
+    /// `NoRoundsLeft`, restored by every received I-block or R-block
+    pub fn wtx_rounds_remaining(&self) -> i32 {
+        self.t1.wtx_rounds_remaining()
+    }
+
+    /// Check if the last response did not fit into the receive buffer.
+    /// The transmission can be retried with a larger buffer.
+    pub fn response_truncated(&self) -> bool {
+        self.t1.response_truncated()
+    }
+
+    /// Protocol statistics counters gathered since the creation or the
+    /// last `reset_stats()` call
+    pub fn stats(&self) -> Stats {
+        self.t1.stats()
+    }
+
+    /// Send and receive sequence numbers of the next I-blocks of the
+    /// current logical channel
+    pub fn sequence_numbers(&self) -> (u8, u8) {
+        self.t1.sequence_numbers()
+    }
+
+    /// Card information field size IFSC taken from the ATR or the last
+    /// S(IFS) request of the card
+    pub fn card_ifs(&self) -> u8 {
+        self.t1.ifs_card()
+    }
+
+    /// Device information field size IFSD negotiated with the card
+    pub fn dev_ifs(&self) -> u8 {
+        self.t1.ifs_dev()
+    }
+
+    /// Clear protocol statistics counters
+    pub fn reset_stats(&mut self) {
+        self.t1.reset_stats();
+    }
+
+    /// Forget the Answer To Reset of the current card, so the next
+    /// `transmit()` or `atr()` call fetches it again. Useful when a
+    /// contactless card may have been replaced by another one.
+    pub fn invalidate_atr(&mut self) {
+        self.t1.clear_atr();
+    }
+
+    /// Get the protocol error of the last failed transmit, ATR or reset,
+    /// cleared by a successful one. Errors a successful exchange recovered
+    /// from are reported by `recovered_error()`.
+    pub fn last_error(&self) -> Option<&proto::Error<E>> {
+        self.last_error.as_ref()
+    }
+
+    /// Take the protocol error of the last failed transmit, ATR or reset
+    pub fn take_last_error(&mut self) -> Option<proto::Error<E>> {
+        self.last_error.take()
     }
 
     /// Release Transmission context
     pub fn release(&mut self) -> Result<(), Error<E>> {
+        if self.released {
+            return Ok(());
+        }
+
+        if let (true, Some(cb)) = (self.inited, self.on_session_end) {
+            cb();
+        }
+
+        if !self.keep_interface {
+            self.release_interface()?;
+        }
+
+        self.t1.set_need_reset();
+        self.inited = false;
+        self.released = true;
+
+        Ok(())
+    }
+
+    fn release_interface(&mut self) -> Result<(), Error<E>> {
         self.interface = match self.release_cb {
             Some(cb) => cb(self.interface.as_ref()).map_err(Error::ReleaseCbErr)?,
             None => None,
         };
 
-        self.inited = false;
-
         Ok(())
     }
 
@@ -214,25 +579,332 @@ impl<'a, T, E> Transmission<'a, T, E> {
 
         Ok(())
     }
+
+    /// Read callback for the blocks, whole block one preferred
+    fn block_read_cb(&self) -> Result<ReadCb<T, E>, Error<E>> {
+        self.read_block_cb.or(self.read_cb).ok_or(Error::NoReadCb)
+    }
+}
+
+// The operations keeping their protocol error as the last error return a
+// copy of it, hence `E: Clone`
+impl<T, E: Clone> Transmission<T, E> {
+    /// Reset Transmission protocol states
+    pub fn reset(&mut self) -> Result<(), Error<E>> {
+        self.try_init()?;
+
+        // Cold reset
+        if let Some(gpio) = self.gpio_reset_cb {
+            let mut clock = self.clock();
+            gpio(true);
+            clock.sleep(self.reset_assert_ms.unwrap_or(RESET_ASSERT_MS));
+            gpio(false);
+            clock.sleep(self.reset_deassert_ms.unwrap_or(RESET_DEASSERT_MS));
+        } else if let Some(cb) = self.reset_cb {
+            cb(self.interface.as_ref()).map_err(Error::ResetCbErr)?
+        } else if self.cold_reset_required {
+            return Err(Error::NoResetCb);
+        }
+
+        // Soft reset
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        let ret = self.t1.reset(|b| read(ifc, b), |b| write(ifc, b));
+        self.track(ret)?;
+
+        if let Some(filter) = self.atr_filter {
+            if self.soft_reset && !filter(self.t1.last_atr()) {
+                return Err(Error::AtrRejected);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get Answer To Reset (ATR)
+    pub fn atr(&mut self) -> Result<&[u8], Error<E>> {
+        self.try_init()?;
+
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+        let ret = self.t1.atr(|b| read(ifc, b), |b| write(ifc, b)).map(|_| ());
+        self.track(ret)?;
+
+        let atr = self.t1.last_atr();
+
+        if let Some(filter) = self.atr_filter {
+            if !filter(atr) {
+                return Err(Error::AtrRejected);
+            }
+        }
+
+        Ok(atr)
+    }
+
+    /// Reset the card and get its Answer To Reset, trying up to `attempts`
+    /// times with a short sleep in between. The last error is returned
+    /// if all of them fail.
+    pub fn atr_with_retries(&mut self, attempts: u8) -> Result<&[u8], Error<E>> {
+        let mut ret = Err(Error::T1(proto::Error::NoAtr));
+
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                self.clock().sleep(ATR_RETRY_MS);
+            }
+            self.t1.clear_atr();
+            ret = self.reset();
+            if ret.is_ok() {
+                break;
+            }
+        }
+        ret?;
+
+        self.atr()
+    }
+
+    /// Transmit APDU data and get the response. Command and response
+    /// APDUs longer than the information field size, such as extended
+    /// length ones, are exchanged in chained blocks.
+    pub fn transmit<'r>(
+        &mut self,
+        capdu: &[u8],
+        rapdu: &'r mut [u8],
+    ) -> Result<&'r [u8], Error<E>> {
+        let n = self.transmit_into(capdu, rapdu)?;
+
+        Ok(&rapdu[..n])
+    }
+
+    /// Transmit a static command APDU, e.g. a constant command table
+    /// entry. The command is never copied nor stored, so `transmit()`
+    /// accepts it as well.
+    pub fn transmit_static<'r>(
+        &mut self,
+        capdu: &'static [u8],
+        rapdu: &'r mut [u8],
+    ) -> Result<&'r [u8], Error<E>> {
+        self.transmit(capdu, rapdu)
+    }
+
+    /// Transmit APDU data on a logical channel addressed by the NAD bytes
+    /// of `set_channel_nad()`, channel 0 being the default one. Each
+    /// channel keeps its own block sequence numbers.
+    pub fn transmit_on<'r>(
+        &mut self,
+        channel: u8,
+        capdu: &[u8],
+        rapdu: &'r mut [u8],
+    ) -> Result<&'r [u8], Error<E>> {
+        self.try_init()?;
+        if !self.t1.select_channel(channel) {
+            return Err(Error::UnknownChannel(channel));
+        }
+
+        let ret = self.transmit_into(capdu, rapdu);
+        self.t1.select_channel(0);
+
+        Ok(&rapdu[..ret?])
+    }
+
+    /// Encode and transmit command APDU, getting the response split into
+    /// data and status words. With wrong Le retry enabled, a 6CXX status
+    /// is answered by re-sending the command once with Le = XX.
+    ///
+    /// Commands with more than 255 data bytes are encoded with
+    /// `Apdu::write()` into a caller buffer and sent with `transmit()`.
+    pub fn transmit_apdu<'r>(
+        &mut self,
+        apdu: &Apdu,
+        rapdu: &'r mut [u8],
+    ) -> Result<Response<'r>, Error<E>> {
+        let mut capdu = [0u8; apdu::APDU_BUF_SIZE];
+        let n = apdu.write(&mut capdu).map_err(Error::Apdu)?;
+        let mut len = self.transmit_into(&capdu[..n], rapdu)?;
+
+        if let (true, [.., SW1_WRONG_LE, sw2]) = (self.wrong_le_retry, &rapdu[..len]) {
+            let le = if *sw2 == 0 { 256 } else { u16::from(*sw2) };
+            let n = apdu.le(le).write(&mut capdu).map_err(Error::Apdu)?;
+            len = self.transmit_into(&capdu[..n], rapdu)?;
+        }
+
+        let rapdu: &'r [u8] = rapdu;
+        Response::parse(&rapdu[..len]).map_err(Error::Apdu)
+    }
+
+    /// Transmit command APDU and receive response APDU into `rapdu`,
+    /// returning the response length
+    pub fn transmit_into(&mut self, capdu: &[u8], rapdu: &mut [u8]) -> Result<usize, Error<E>> {
+        self.try_init()?;
+
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+
+        let ret = self
+            .t1
+            .transmit(capdu, rapdu, |b| read(ifc, b), |b| write(ifc, b));
+        self.track(ret)
+    }
+
+    /// Transmit command APDU like `transmit_into()`, writing a human
+    /// readable line for each sent and received block to `trace`
+    pub fn transmit_traced<TR: fmt::Write>(
+        &mut self,
+        capdu: &[u8],
+        rapdu: &mut [u8],
+        trace: &mut TR,
+    ) -> Result<usize, Error<E>> {
+        self.try_init()?;
+
+        let ifc = self.interface.as_ref();
+        let read = self.block_read_cb()?;
+        let write = self.write_cb.as_ref().ok_or(Error::NoWriteCb)?;
+
+        let ret = self
+            .t1
+            .transmit_traced(capdu, rapdu, |b| read(ifc, b), |b| write(ifc, b), trace);
+        self.track(ret)
+    }
+
+    /// Re-establish card connection: release, initialize and reset
+    pub fn reconnect(&mut self) -> Result<(), Error<E>> {
+        self.release().unwrap_or(());
+
+        let ret = self.init().and_then(|_| self.reset());
+        if ret.is_err() {
+            self.inited = false;
+        }
+
+        ret
+    }
+
+    fn clock(&self) -> Clock {
+        match self.sim_time {
+            Some(sim) => Clock::new_simulated(0, sim),
+            None => Clock::new(0, self.sleep_cb, self.now_cb),
+        }
+    }
+
+    /// Keep the protocol error of a failed operation as the last error,
+    /// returning a copy of it, clearing it on success
+    fn track<R>(&mut self, ret: Result<R, proto::Error<E>>) -> Result<R, Error<E>> {
+        match ret {
+            Ok(v) => {
+                self.last_error = None;
+                Ok(v)
+            }
+            Err(err) => {
+                let copy = err.clone();
+                self.last_error = Some(err);
+                Err(Error::T1(copy))
+            }
+        }
+    }
 }
 
-impl<T, E> Drop for Transmission<'_, T, E> {
+impl<T, E> Drop for Transmission<T, E> {
     fn drop(&mut self) {
-        self.release().unwrap_or(())
+        self.release().unwrap_or(());
+        if self.keep_interface && self.interface.is_some() {
+            self.release_interface().unwrap_or(());
+        }
     }
 }
 
-/// ISO7816 Transmission context Builder
-pub struct TransmissionBuilder<T, E> {
+/// Connection interface and callbacks recovered from a Transmission
+pub struct TransmissionParts<T, E> {
+    /// Smart Card communication interface context
+    pub interface: Option<T>,
+
+    /// Connection interface initialization callback
+    pub init_cb: Option<InitCb<T, E>>,
+
+    /// Connection interface release callback
+    pub release_cb: Option<ReleaseCb<T, E>>,
+
+    /// Connection interface reset callback
+    pub reset_cb: Option<ResetCb<T, E>>,
+
+    /// RST pin control callback
+    pub gpio_reset_cb: Option<GpioResetCb>,
+
+    /// Connection interface read callback
+    pub read_cb: Option<ReadCb<T, E>>,
+
+    /// Connection interface whole block read callback
+    pub read_block_cb: Option<ReadCb<T, E>>,
+
+    /// Connection interface write callback
+    pub write_cb: Option<WriteCb<T, E>>,
+
+    /// Timer sleeping callback
+    pub sleep_cb: Option<fn(u32)>,
+
+    /// Monotonic clock callback
+    pub now_cb: Option<NowCb>,
+
+    /// Simulated time replacing the sleeping and clock callbacks
+    pub sim_time: Option<&'static SimulatedTime>,
+
+    /// Cooperative scheduler yielding callback
+    pub yield_cb: Option<fn()>,
+}
+
+/// Typestate marker of a required builder option which is not set yet
+pub struct Unset;
+
+/// Typestate marker of a required builder option which is set
+pub struct Set;
+
+/// ISO7816 Transmission context Builder. The type parameters track the
+/// required read, write and sleep callbacks and card and device NAD bytes,
+/// `build()` is only available once all of them are set.
+pub struct TransmissionBuilder<T, E, R = Unset, W = Unset, S = Unset, C = Unset, D = Unset> {
     init_cb: Option<InitCb<T, E>>,
     release_cb: Option<ReleaseCb<T, E>>,
     reset_cb: Option<ResetCb<T, E>>,
+    gpio_reset_cb: Option<GpioResetCb>,
+    reset_assert_ms: Option<u32>,
+    reset_deassert_ms: Option<u32>,
     read_cb: Option<ReadCb<T, E>>,
+    read_block_cb: Option<ReadCb<T, E>>,
     write_cb: Option<WriteCb<T, E>>,
     sleep_cb: Option<fn(u32)>,
+    now_cb: Option<NowCb>,
+    sim_time: Option<&'static SimulatedTime>,
+    abort_flag: Option<&'static AtomicBool>,
+    yield_cb: Option<fn()>,
+    wait_progress_cb: Option<fn(u32)>,
+    on_session_start: Option<fn()>,
+    on_session_end: Option<fn()>,
+    #[cfg(feature = "trace")]
+    state_cb: Option<fn(&str)>,
+    trace_cb: Option<TraceCb>,
+    channel_nads: [Option<(u8, u8)>; CHANNELS_MAX],
+    invalid_channel: Option<u8>,
     card_nad: Option<u8>,
     dev_nad: Option<u8>,
+    nad_timeout: Option<u32>,
+    total_timeout: Option<u32>,
+    noise_max: Option<usize>,
+    cwt: Option<u32>,
+    atr_filter: Option<AtrFilter>,
+    recv_max: Option<usize>,
+    buf_size: Option<usize>,
+    wtx_max_value: Option<u8>,
+    wtx_max_rounds: Option<i32>,
+    chk_algo: ChkAlgo,
     soft_reset: bool,
+    lenient_pcb: bool,
+    any_card_nad: bool,
+    wrong_le_retry: bool,
+    chain_warnings: bool,
+    auto_get_response: bool,
+    cold_reset_required: bool,
+    keep_interface: bool,
+    state: PhantomData<(R, W, S, C, D)>,
 }
 
 impl<T, E> TransmissionBuilder<T, E> {
@@ -242,12 +914,101 @@ impl<T, E> TransmissionBuilder<T, E> {
             init_cb: None,
             release_cb: None,
             reset_cb: None,
+            gpio_reset_cb: None,
+            reset_assert_ms: None,
+            reset_deassert_ms: None,
             read_cb: None,
+            read_block_cb: None,
             write_cb: None,
             sleep_cb: None,
+            now_cb: None,
+            sim_time: None,
+            abort_flag: None,
+            yield_cb: None,
+            wait_progress_cb: None,
+            on_session_start: None,
+            on_session_end: None,
+            #[cfg(feature = "trace")]
+            state_cb: None,
+            trace_cb: None,
+            channel_nads: [None; CHANNELS_MAX],
+            invalid_channel: None,
             card_nad: None,
             dev_nad: None,
+            nad_timeout: None,
+            total_timeout: None,
+            noise_max: None,
+            cwt: None,
+            atr_filter: None,
+            recv_max: None,
+            buf_size: None,
+            wtx_max_value: None,
+            wtx_max_rounds: None,
+            chk_algo: ChkAlgo::Lrc,
             soft_reset: false,
+            lenient_pcb: false,
+            any_card_nad: false,
+            wrong_le_retry: false,
+            chain_warnings: false,
+            auto_get_response: false,
+            cold_reset_required: false,
+            keep_interface: false,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<T, E, R, W, S, C, D> TransmissionBuilder<T, E, R, W, S, C, D> {
+    /// Copy of the builder, to configure several similar transmissions
+    pub fn replicate(&self) -> Self {
+        *self
+    }
+
+    fn into_state<R2, W2, S2, C2, D2>(self) -> TransmissionBuilder<T, E, R2, W2, S2, C2, D2> {
+        TransmissionBuilder {
+            init_cb: self.init_cb,
+            release_cb: self.release_cb,
+            reset_cb: self.reset_cb,
+            gpio_reset_cb: self.gpio_reset_cb,
+            reset_assert_ms: self.reset_assert_ms,
+            reset_deassert_ms: self.reset_deassert_ms,
+            read_cb: self.read_cb,
+            read_block_cb: self.read_block_cb,
+            write_cb: self.write_cb,
+            sleep_cb: self.sleep_cb,
+            now_cb: self.now_cb,
+            sim_time: self.sim_time,
+            abort_flag: self.abort_flag,
+            yield_cb: self.yield_cb,
+            wait_progress_cb: self.wait_progress_cb,
+            on_session_start: self.on_session_start,
+            on_session_end: self.on_session_end,
+            #[cfg(feature = "trace")]
+            state_cb: self.state_cb,
+            trace_cb: self.trace_cb,
+            channel_nads: self.channel_nads,
+            invalid_channel: self.invalid_channel,
+            card_nad: self.card_nad,
+            dev_nad: self.dev_nad,
+            nad_timeout: self.nad_timeout,
+            total_timeout: self.total_timeout,
+            noise_max: self.noise_max,
+            cwt: self.cwt,
+            atr_filter: self.atr_filter,
+            recv_max: self.recv_max,
+            buf_size: self.buf_size,
+            wtx_max_value: self.wtx_max_value,
+            wtx_max_rounds: self.wtx_max_rounds,
+            chk_algo: self.chk_algo,
+            soft_reset: self.soft_reset,
+            lenient_pcb: self.lenient_pcb,
+            any_card_nad: self.any_card_nad,
+            wrong_le_retry: self.wrong_le_retry,
+            chain_warnings: self.chain_warnings,
+            auto_get_response: self.auto_get_response,
+            cold_reset_required: self.cold_reset_required,
+            keep_interface: self.keep_interface,
+            state: PhantomData,
         }
     }
 
@@ -272,32 +1033,257 @@ impl<T, E> TransmissionBuilder<T, E> {
         self
     }
 
+    /// Set RST pin control callback, called with `true` to assert RST and
+    /// `false` to de-assert it. Used for cold reset instead of the reset
+    /// callback.
+    pub fn set_gpio_reset_cb(mut self, cb: GpioResetCb) -> Self {
+        self.gpio_reset_cb = Some(cb);
+
+        self
+    }
+
+    /// Set RST pin assertion time in milliseconds
+    pub fn set_reset_assert_delay_ms(mut self, ms: u32) -> Self {
+        self.reset_assert_ms = Some(ms);
+
+        self
+    }
+
+    /// Set delay in milliseconds after RST pin de-assertion
+    pub fn set_reset_deassert_delay_ms(mut self, ms: u32) -> Self {
+        self.reset_deassert_ms = Some(ms);
+
+        self
+    }
+
     /// Set connection interface read callback
-    pub fn set_read_cb(mut self, cb: ReadCb<T, E>) -> Self {
+    pub fn set_read_cb(mut self, cb: ReadCb<T, E>) -> TransmissionBuilder<T, E, Set, W, S, C, D> {
         self.read_cb = Some(cb);
 
-        self
+        self.into_state()
+    }
+
+    /// Set connection interface callback reading a whole block at once.
+    /// Replaces the byte by byte polling for the blocks, `read_cb` is
+    /// still used for PPS exchange.
+    pub fn set_read_block_cb(
+        mut self,
+        cb: ReadCb<T, E>,
+    ) -> TransmissionBuilder<T, E, Set, W, S, C, D> {
+        self.read_block_cb = Some(cb);
+
+        self.into_state()
     }
 
     /// Set connection interface write callback
-    pub fn set_write_cb(mut self, cb: WriteCb<T, E>) -> Self {
+    pub fn set_write_cb(mut self, cb: WriteCb<T, E>) -> TransmissionBuilder<T, E, R, Set, S, C, D> {
         self.write_cb = Some(cb);
 
-        self
+        self.into_state()
     }
 
     /// Set timer sleeping callback
-    pub fn set_sleep_cb(mut self, cb: fn(u32)) -> Self {
+    pub fn set_sleep_cb(mut self, cb: fn(u32)) -> TransmissionBuilder<T, E, R, W, Set, C, D> {
         self.sleep_cb = Some(cb);
 
+        self.into_state()
+    }
+
+    /// Set monotonic clock callback returning milliseconds. Timeouts are
+    /// measured in real time, including time spent in the read callback,
+    /// and it is used for busy-waiting when no sleeping callback is set.
+    /// Without it only sleeping time counts toward timeouts.
+    pub fn set_now_cb(mut self, cb: NowCb) -> TransmissionBuilder<T, E, R, W, Set, C, D> {
+        self.now_cb = Some(cb);
+
+        self.into_state()
+    }
+
+    /// Measure the timeouts in simulated time advanced by the protocol
+    /// sleeps, so the timeout behavior is tested without waiting. Used in
+    /// place of the sleeping and clock callbacks.
+    pub fn set_simulated_time(
+        mut self,
+        time: &'static SimulatedTime,
+    ) -> TransmissionBuilder<T, E, R, W, Set, C, D> {
+        self.sim_time = Some(time);
+
+        self.into_state()
+    }
+
+    /// Set the flag requesting the current exchange to be aborted, e.g.
+    /// from an interrupt. The next block sent is S(ABORT request) and the
+    /// exchange ends with `Aborted` once the card responds, polling for
+    /// the card response stops early. Cleared when an exchange begins.
+    ///
+    /// `abort_transmit()` cannot be called while `transmit()` borrows the
+    /// Transmission, so an abort from another context goes through this
+    /// flag. It is `'static` because interrupt handlers and the `fn`
+    /// callbacks can only reach statics.
+    pub fn set_abort_flag(mut self, flag: &'static AtomicBool) -> Self {
+        self.abort_flag = Some(flag);
+
+        self
+    }
+
+    /// Set cooperative scheduler yielding callback, called between block
+    /// exchanges and while waiting for the card
+    pub fn set_yield_cb(mut self, cb: fn()) -> Self {
+        self.yield_cb = Some(cb);
+
+        self
+    }
+
+    /// Set callback called while waiting for the card response with the
+    /// remaining block waiting time in milliseconds, e.g. to feed a watchdog
+    pub fn set_wait_progress_cb(mut self, cb: fn(u32)) -> Self {
+        self.wait_progress_cb = Some(cb);
+
+        self
+    }
+
+    /// Set callback called when a card session starts after successful
+    /// initialization, e.g. to turn on an activity LED
+    pub fn set_on_session_start(mut self, cb: fn()) -> Self {
+        self.on_session_start = Some(cb);
+
+        self
+    }
+
+    /// Set callback called when a card session ends, before the connection
+    /// interface is released
+    pub fn set_on_session_end(mut self, cb: fn()) -> Self {
+        self.on_session_end = Some(cb);
+
+        self
+    }
+
+    /// Set protocol state transition tracing callback
+    #[cfg(feature = "trace")]
+    pub fn set_state_cb(mut self, cb: fn(&str)) -> Self {
+        self.state_cb = Some(cb);
+
+        self
+    }
+
+    /// Set raw block tracing callback, called with each block exactly as
+    /// written and as received, including the NAD, PCB, LEN and EDC fields
+    pub fn set_trace_cb(mut self, cb: TraceCb) -> Self {
+        self.trace_cb = Some(cb);
+
         self
     }
 
     /// Set NAD bytes for Smart Card and Device
-    pub fn set_nad(mut self, card_nad: u8, dev_nad: u8) -> Self {
+    pub fn set_nad(
+        self,
+        card_nad: u8,
+        dev_nad: u8,
+    ) -> TransmissionBuilder<T, E, R, W, S, Set, Set> {
+        self.set_card_nad(card_nad).set_device_nad(dev_nad)
+    }
+
+    /// Set Smart Card NAD byte
+    pub fn set_card_nad(mut self, card_nad: u8) -> TransmissionBuilder<T, E, R, W, S, Set, D> {
         self.card_nad = Some(card_nad);
+
+        self.into_state()
+    }
+
+    /// Set Device NAD byte
+    pub fn set_device_nad(mut self, dev_nad: u8) -> TransmissionBuilder<T, E, R, W, S, C, Set> {
         self.dev_nad = Some(dev_nad);
 
+        self.into_state()
+    }
+
+    /// Set Smart Card and Device NAD bytes addressing logical channel 1
+    /// to 3 in `transmit_on()`. Channel 0 uses the NAD bytes of
+    /// `set_nad()`, other channel numbers are reported by `validate()`.
+    pub fn set_channel_nad(mut self, channel: u8, card_nad: u8, dev_nad: u8) -> Self {
+        match self.channel_nads.get_mut(usize::from(channel)) {
+            Some(nad) if channel != 0 => *nad = Some((card_nad, dev_nad)),
+            _ => self.invalid_channel = self.invalid_channel.or(Some(channel)),
+        }
+
+        self
+    }
+
+    /// Set upper bound in milliseconds of a whole exchange, including the
+    /// waiting time extensions and retries. Exceeding it fails with
+    /// `TotalTimeout`.
+    pub fn set_total_timeout(mut self, timeout: u32) -> Self {
+        self.total_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Set timeout in milliseconds for receiving the Smart Card NAD byte,
+    /// usually shorter than the block waiting time
+    pub fn set_nad_timeout(mut self, timeout: u32) -> Self {
+        self.nad_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Set maximum number of noise bytes discarded while waiting for the
+    /// Smart Card NAD byte
+    pub fn set_noise_max(mut self, max: usize) -> Self {
+        self.noise_max = Some(max);
+
+        self
+    }
+
+    /// Set character waiting time in milliseconds. Short reads within a
+    /// block are retried until no more characters arrive for this time.
+    pub fn set_cwt(mut self, cwt: u32) -> Self {
+        self.cwt = Some(cwt);
+
+        self
+    }
+
+    /// Set Answer To Reset matcher, the card is rejected if it returns false
+    pub fn set_atr_filter(mut self, filter: AtrFilter) -> Self {
+        self.atr_filter = Some(filter);
+
+        self
+    }
+
+    /// Set maximum response APDU length, at least 2 bytes for SW1-SW2
+    pub fn set_recv_max(mut self, max: usize) -> Self {
+        self.recv_max = Some(max);
+
+        self
+    }
+
+    /// Set block buffer size, including prologue and epilogue fields, for
+    /// transports with a smaller frame buffer. Up to 260 bytes.
+    pub fn set_block_buf_size(mut self, size: usize) -> Self {
+        self.buf_size = Some(size);
+
+        self
+    }
+
+    /// Set maximum accepted waiting time extension multiplier, larger
+    /// S(WTX request) values are clamped. Zero disables the limit, default 1.
+    pub fn set_wtx_max_value(mut self, v: u8) -> Self {
+        self.wtx_max_value = Some(v);
+
+        self
+    }
+
+    /// Set maximum number of consecutive S(WTX request) blocks before the
+    /// transmission fails. Zero disables the limit, default 200.
+    pub fn set_wtx_max_rounds(mut self, rounds: i32) -> Self {
+        self.wtx_max_rounds = Some(rounds);
+
+        self
+    }
+
+    /// Set block error detection code, LRC by default
+    pub fn set_chk_algo(mut self, algo: ChkAlgo) -> Self {
+        self.chk_algo = algo;
+
         self
     }
 
@@ -308,25 +1294,168 @@ impl<T, E> TransmissionBuilder<T, E> {
         self
     }
 
-    /// Build Transmission structure from setuped TransmissionBuilder
-    pub fn build<'a>(self) -> Transmission<'a, T, E> {
+    /// Enable lenient PCB validation for cards setting reserved bits
+    pub fn enable_lenient_pcb(mut self) -> Self {
+        self.lenient_pcb = true;
+
+        self
+    }
+
+    /// Accept blocks with any source NAD, e.g. relayed by a gateway
+    /// rewriting the NAD. The block structure is still validated.
+    pub fn accept_any_card_nad(mut self) -> Self {
+        self.any_card_nad = true;
+
+        self
+    }
+
+    /// Enable re-sending command APDU once with the Le from SW2 when
+    /// `transmit_apdu()` gets 6CXX status, 6C00 meaning Le = 256
+    pub fn enable_wrong_le_retry(mut self) -> Self {
+        self.wrong_le_retry = true;
+
+        self
+    }
+
+    /// Enable accepting 63XX warning status for the intermediate commands
+    /// of `transmit_chained()`
+    pub fn enable_chain_warnings(mut self) -> Self {
+        self.chain_warnings = true;
+
+        self
+    }
+
+    /// Enable fetching the remaining response bytes announced with 61XX
+    /// status by GET RESPONSE in `select_aid()`
+    pub fn enable_auto_get_response(mut self) -> Self {
+        self.auto_get_response = true;
+
+        self
+    }
+
+    /// Require a cold reset callback, `reset()` fails with `NoResetCb`
+    /// if neither reset nor RST pin callback is set
+    pub fn require_cold_reset(mut self) -> Self {
+        self.cold_reset_required = true;
+
+        self
+    }
+
+    /// Keep the connection interface open across `release()` and `init()`
+    /// calls, the release callback is only called on drop
+    pub fn keep_interface(mut self) -> Self {
+        self.keep_interface = true;
+
+        self
+    }
+
+    /// Check the required options without consuming the builder, listing
+    /// all the missing ones
+    #[allow(clippy::result_large_err)]
+    pub fn validate(&self) -> Result<(), BuildError> {
+        let mut missing = heapless::Vec::new();
+        let required = [
+            ("read_cb", self.read_cb.or(self.read_block_cb).is_some()),
+            ("write_cb", self.write_cb.is_some()),
+            (
+                "sleep_cb",
+                self.sleep_cb.is_some() || self.now_cb.is_some() || self.sim_time.is_some(),
+            ),
+            ("card_nad", self.card_nad.is_some()),
+            ("dev_nad", self.dev_nad.is_some()),
+        ];
+        for (name, _) in required.iter().filter(|(_, set)| !set) {
+            // Capacity covers all the required options
+            let _ = missing.push(*name);
+        }
+
+        if missing.is_empty() && self.invalid_channel.is_none() {
+            Ok(())
+        } else {
+            Err(BuildError {
+                missing,
+                invalid_channel: self.invalid_channel,
+            })
+        }
+    }
+
+    /// Build Transmission structure without checking the required options
+    /// at compile time, missing ones are reported by `init()`
+    pub fn build_unchecked(self) -> Transmission<T, E> {
         Transmission {
             t1: T1Proto::default(),
             interface: None,
             init_cb: self.init_cb,
             release_cb: self.release_cb,
             reset_cb: self.reset_cb,
+            gpio_reset_cb: self.gpio_reset_cb,
+            reset_assert_ms: self.reset_assert_ms,
+            reset_deassert_ms: self.reset_deassert_ms,
             read_cb: self.read_cb,
+            read_block_cb: self.read_block_cb,
             write_cb: self.write_cb,
             sleep_cb: self.sleep_cb,
+            now_cb: self.now_cb,
+            sim_time: self.sim_time,
+            abort_flag: self.abort_flag,
+            yield_cb: self.yield_cb,
+            wait_progress_cb: self.wait_progress_cb,
+            on_session_start: self.on_session_start,
+            on_session_end: self.on_session_end,
+            #[cfg(feature = "trace")]
+            state_cb: self.state_cb,
+            trace_cb: self.trace_cb,
+            channel_nads: self.channel_nads,
             card_nad: self.card_nad,
             dev_nad: self.dev_nad,
+            nad_timeout: self.nad_timeout,
+            total_timeout: self.total_timeout,
+            noise_max: self.noise_max,
+            cwt: self.cwt,
+            atr_filter: self.atr_filter,
+            recv_max: self.recv_max,
+            buf_size: self.buf_size,
+            wtx_max_value: self.wtx_max_value,
+            wtx_max_rounds: self.wtx_max_rounds,
+            chk_algo: self.chk_algo,
             inited: false,
+            released: false,
             soft_reset: self.soft_reset,
+            lenient_pcb: self.lenient_pcb,
+            any_card_nad: self.any_card_nad,
+            wrong_le_retry: self.wrong_le_retry,
+            chain_warnings: self.chain_warnings,
+            auto_get_response: self.auto_get_response,
+            cold_reset_required: self.cold_reset_required,
+            keep_interface: self.keep_interface,
+            last_error: None,
         }
     }
 }
 
+impl<T, E> TransmissionBuilder<T, E, Set, Set, Set, Set, Set> {
+    /// Build Transmission structure from setuped TransmissionBuilder
+    pub fn build(self) -> Transmission<T, E> {
+        self.build_unchecked()
+    }
+
+    /// Build `N` identical Transmission structures, e.g. for several
+    /// readers of the same kind
+    pub fn build_n<const N: usize>(self) -> [Transmission<T, E>; N] {
+        core::array::from_fn(|_| self.build())
+    }
+}
+
+// Manual impls: all fields are `Copy` whatever `T` and `E` are, which
+// the derive would require to be `Copy` as well
+impl<T, E, R, W, S, C, D> Clone for TransmissionBuilder<T, E, R, W, S, C, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, E, R, W, S, C, D> Copy for TransmissionBuilder<T, E, R, W, S, C, D> {}
+
 impl<T, E> Default for TransmissionBuilder<T, E> {
     fn default() -> Self {
         Self::new()
@@ -335,6 +1464,8 @@ impl<T, E> Default for TransmissionBuilder<T, E> {
 
 /// ISO7816 Transmission errors
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error<E> {
     /// ISO/IEC 7816 T=1 transmission protocol context
     T1(proto::Error<E>),
@@ -357,9 +1488,368 @@ pub enum Error<E> {
     /// Connection interface write callback is not set
     NoWriteCb,
 
-    /// Timer sleep callback is not set
+    /// Neither timer sleep nor monotonic clock callback is set
     NoSleepCb,
 
+    /// Cold reset is required, but no reset callback is set
+    NoResetCb,
+
     /// Doulble initializing method call
     AlreadyInited,
+
+    /// Answer To Reset is rejected by the matcher
+    AtrRejected,
+
+    /// Maximum response APDU length is too small
+    RecvMaxTooSmall(usize),
+
+    /// Work buffer has no room for the next GET RESPONSE command
+    WorkBufTooSmall(usize),
+
+    /// Command APDU encoding or response APDU parsing failed
+    Apdu(ApduError),
+
+    /// Writing the hex encoded response failed
+    HexWrite,
+
+    /// Intermediate command of a chain was answered with this status
+    ChainRejected(u16),
+
+    /// Logical channel NAD bytes are not set
+    UnknownChannel(u8),
+}
+
+/// Required TransmissionBuilder options left unconfigured or invalid
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildError {
+    /// Names of the missing options
+    pub missing: heapless::Vec<&'static str, 8>,
+
+    /// First logical channel given to `set_channel_nad()` out of 1 to 3
+    pub invalid_channel: Option<u8>,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.missing.is_empty() {
+            f.write_str("missing builder options:")?;
+            for name in &self.missing {
+                write!(f, " {name}")?;
+            }
+        }
+        if let Some(ch) = self.invalid_channel {
+            if !self.missing.is_empty() {
+                f.write_str(", ")?;
+            }
+            write!(f, "invalid logical channel {ch}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::T1(e) => write!(f, "T=1 protocol error: {e}"),
+            Self::InitCbErr(e) => write!(f, "interface initialization failed: {e}"),
+            Self::ReleaseCbErr(e) => write!(f, "interface release failed: {e}"),
+            Self::ResetCbErr(e) => write!(f, "interface reset failed: {e}"),
+            Self::NadNotSet => f.write_str("NAD byte is not set"),
+            Self::NoReadCb => f.write_str("read callback is not set"),
+            Self::NoWriteCb => f.write_str("write callback is not set"),
+            Self::NoSleepCb => f.write_str("neither sleep nor clock callback is set"),
+            Self::NoResetCb => f.write_str("cold reset required, but no reset callback is set"),
+            Self::AlreadyInited => f.write_str("already initialized"),
+            Self::AtrRejected => f.write_str("Answer To Reset rejected"),
+            Self::RecvMaxTooSmall(n) => write!(f, "maximum response length {n} is too small"),
+            Self::WorkBufTooSmall(n) => write!(f, "work buffer of {n} bytes is too small"),
+            Self::Apdu(e) => write!(f, "APDU error: {e}"),
+            Self::HexWrite => f.write_str("writing hex encoded response failed"),
+            Self::ChainRejected(sw) => write!(f, "command chain rejected with status {sw:04X}"),
+            Self::UnknownChannel(ch) => write!(f, "logical channel {ch} is not set"),
+        }
+    }
+}
+
+impl<E> Error<E> {
+    /// T=1 transmission protocol error, if any
+    pub fn as_t1(&self) -> Option<&T1Error<E>> {
+        match self {
+            Self::T1(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Check for a T=1 protocol waiting time expiration
+    pub fn is_timeout(&self) -> bool {
+        self.as_t1().is_some_and(T1Error::is_timeout)
+    }
+
+    /// Check for a T=1 block error detection code mismatch
+    pub fn is_crc_error(&self) -> bool {
+        self.as_t1().is_some_and(T1Error::is_crc_error)
+    }
+
+    /// Convert the interface error type, e.g. to wrap the errors of
+    /// several transports into one type
+    pub fn map_io_err<F>(self, f: impl Fn(E) -> F) -> Error<F> {
+        match self {
+            Self::T1(v) => Error::T1(v.map_io_err(f)),
+            Self::InitCbErr(v) => Error::InitCbErr(f(v)),
+            Self::ReleaseCbErr(v) => Error::ReleaseCbErr(f(v)),
+            Self::ResetCbErr(v) => Error::ResetCbErr(f(v)),
+            Self::NadNotSet => Error::NadNotSet,
+            Self::NoReadCb => Error::NoReadCb,
+            Self::NoWriteCb => Error::NoWriteCb,
+            Self::NoSleepCb => Error::NoSleepCb,
+            Self::NoResetCb => Error::NoResetCb,
+            Self::AlreadyInited => Error::AlreadyInited,
+            Self::AtrRejected => Error::AtrRejected,
+            Self::RecvMaxTooSmall(v) => Error::RecvMaxTooSmall(v),
+            Self::WorkBufTooSmall(v) => Error::WorkBufTooSmall(v),
+            Self::Apdu(v) => Error::Apdu(v),
+            Self::HexWrite => Error::HexWrite,
+            Self::ChainRejected(v) => Error::ChainRejected(v),
+            Self::UnknownChannel(v) => Error::UnknownChannel(v),
+        }
+    }
+}
+
+impl Error<Infallible> {
+    /// Convert an error of an infallible interface to any interface
+    /// error type
+    pub fn into_io_err<F>(self) -> Error<F> {
+        self.map_io_err(|e| match e {})
+    }
+}
+
+/// Interface errors are reported as the error source
+///
+/// ```
+/// use iso7816_tx::Transmission;
+/// use std::fs::File;
+///
+/// fn reopen(t: &mut Transmission<File, std::io::Error>) -> anyhow::Result<()> {
+///     t.release()?;
+///     t.init()?;
+///
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::T1(e) => Some(e),
+            Self::InitCbErr(e) | Self::ReleaseCbErr(e) | Self::ResetCbErr(e) => Some(e),
+            Self::Apdu(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+/// Convert to an I/O error, e.g. for the `?` operator in functions
+/// returning `std::io::Result`. The interface errors are passed through.
+#[cfg(feature = "std")]
+impl From<Error<std::io::Error>> for std::io::Error {
+    fn from(err: Error<std::io::Error>) -> Self {
+        match err {
+            Error::InitCbErr(e) | Error::ReleaseCbErr(e) | Error::ResetCbErr(e) => e,
+            Error::T1(proto::Error::ReadNad(e))
+            | Error::T1(proto::Error::ReadHdr(e))
+            | Error::T1(proto::Error::ReadData(e))
+            | Error::T1(proto::Error::Write(e)) => e,
+            e => std::io::Error::other(e),
+        }
+    }
+}
+
+impl<E> From<proto::Error<E>> for Error<E> {
+    fn from(err: proto::Error<E>) -> Self {
+        Error::T1(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        use std::string::ToString;
+
+        let err: Error<&str> = proto::Error::BadCrc(0x35, 0x00).into();
+        assert_eq!(
+            err.to_string(),
+            "T=1 protocol error: bad checksum: computed 0x35, received 0x00"
+        );
+
+        let err: Error<&str> = Error::InitCbErr("no device");
+        assert_eq!(
+            err.to_string(),
+            "interface initialization failed: no device"
+        );
+
+        let err: Error<&str> = proto::Error::RetriesExhausted(RetryCause::Timeout).into();
+        assert_eq!(
+            err.to_string(),
+            "T=1 protocol error: retries exhausted: timeout"
+        );
+
+        let err: Error<&str> = Error::Apdu(ApduError::AidLen(3));
+        assert_eq!(err.to_string(), "APDU error: invalid AID length 3");
+    }
+
+    #[test]
+    fn test_error_from_t1() {
+        let err: Error<()> = proto::Error::Aborted.into();
+        assert_eq!(err, Error::T1(proto::Error::Aborted));
+
+        fn propagate() -> Result<(), Error<()>> {
+            Err(proto::Error::Timeout(300))?
+        }
+        assert_eq!(propagate(), Err(Error::T1(proto::Error::Timeout(300))));
+    }
+
+    #[test]
+    fn test_error_map_io_err() {
+        use std::string::{String, ToString};
+
+        assert_eq!(
+            Error::InitCbErr(42u8).map_io_err(|e| e.to_string()),
+            Error::InitCbErr("42".to_string())
+        );
+
+        let err: Error<u8> = proto::Error::ReadHdr(7).into();
+        assert_eq!(
+            err.map_io_err(u16::from),
+            Error::T1(proto::Error::ReadHdr(7u16))
+        );
+
+        let err: Error<Infallible> = Error::T1(proto::Error::Timeout(300));
+        let err: Error<String> = err.into_io_err();
+        assert_eq!(err, Error::T1(proto::Error::Timeout(300)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_source() {
+        use std::error::Error as _;
+        use std::io;
+
+        let err: Error<io::Error> = proto::Error::Write(io::ErrorKind::BrokenPipe.into()).into();
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "writing block failed: broken pipe");
+        assert!(source.source().is_some());
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::BrokenPipe);
+
+        let err: Error<io::Error> = Error::NadNotSet;
+        assert!(err.source().is_none());
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::Other);
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn test_error_defmt() {
+        // Encoding needs the target logger, check that the errors and
+        // reported types are formattable
+        fn format<F: defmt::Format>(f: F) -> F {
+            f
+        }
+
+        let err: Error<u8> = proto::Error::RetriesExhausted(RetryCause::BadCrc).into();
+        assert_eq!(
+            format(err),
+            Error::T1(proto::Error::RetriesExhausted(RetryCause::BadCrc))
+        );
+        assert_eq!(
+            format(Error::<u8>::Apdu(ApduError::AidLen(3))),
+            Error::Apdu(ApduError::AidLen(3))
+        );
+        assert_eq!(format(Stats::default()), Stats::default());
+        assert_eq!(format(atr::AtrInfo::default()), atr::AtrInfo::default());
+        assert_eq!(format(StatusWord::Success), StatusWord::Success);
+    }
+}
+
+#[cfg(doctest)]
+mod builder_typestate {
+    /// Complete builder
+    /// ```
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_read_cb(|_, _| Ok(0))
+    ///     .set_write_cb(|_, buf| Ok(buf.len()))
+    ///     .set_sleep_cb(|_| ())
+    ///     .set_nad(0x15, 0x51)
+    ///     .build();
+    /// ```
+    struct Complete;
+
+    /// Missing read callback
+    /// ```compile_fail
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_write_cb(|_, buf| Ok(buf.len()))
+    ///     .set_sleep_cb(|_| ())
+    ///     .set_nad(0x15, 0x51)
+    ///     .build();
+    /// ```
+    struct NoRead;
+
+    /// Missing write callback
+    /// ```compile_fail
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_read_cb(|_, _| Ok(0))
+    ///     .set_sleep_cb(|_| ())
+    ///     .set_nad(0x15, 0x51)
+    ///     .build();
+    /// ```
+    struct NoWrite;
+
+    /// Missing sleep callback
+    /// ```compile_fail
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_read_cb(|_, _| Ok(0))
+    ///     .set_write_cb(|_, buf| Ok(buf.len()))
+    ///     .set_nad(0x15, 0x51)
+    ///     .build();
+    /// ```
+    struct NoSleep;
+
+    /// Missing device NAD
+    /// ```compile_fail
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_read_cb(|_, _| Ok(0))
+    ///     .set_write_cb(|_, buf| Ok(buf.len()))
+    ///     .set_sleep_cb(|_| ())
+    ///     .set_card_nad(0x15)
+    ///     .build();
+    /// ```
+    struct NoDeviceNad;
+
+    /// Missing card NAD
+    /// ```compile_fail
+    /// use iso7816_tx::{Transmission, TransmissionBuilder};
+    ///
+    /// let t: Transmission<(), ()> = TransmissionBuilder::new()
+    ///     .set_read_cb(|_, _| Ok(0))
+    ///     .set_write_cb(|_, buf| Ok(buf.len()))
+    ///     .set_sleep_cb(|_| ())
+    ///     .set_device_nad(0x51)
+    ///     .build();
+    /// ```
+    struct NoCardNad;
 }
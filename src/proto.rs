@@ -4,7 +4,17 @@
 //! https://android.googlesource.com/platform/hardware/st/secure_element2/+/refs/tags/platform-tools-30.0.4/esehal/src/iso7816_t1.c
 //!
 
-use crate::clock::Clock;
+use crate::atr;
+use crate::block::{
+    self, block_kind, is_chaining, BlockKind, ChkAlgo, SBlockDir, SBlockRequest, HEADER_LEN,
+};
+use crate::clock::{Clock, SimulatedTime};
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
 
 /// The Answer To Reset (ATR) ISO/IEC 7816-3 maximum length
 const ATR_SIZE: usize = 32;
@@ -12,38 +22,152 @@ const ATR_SIZE: usize = 32;
 /// 3 bytes header + 254 bytes data + 2 bytes Crc
 const BUF_SIZE: usize = 3 + 255 + 2;
 
+/// Default block waiting time in milliseconds
+const BWT_DEFAULT: u32 = 300;
+
 const MAX_RETRIES: u8 = 3;
-const MAX_WTX_ROUNDS: i32 = 200; // wtx_max_rounds == MAX_WTX_ROUNDS
+const MAX_WTX_ROUNDS: i32 = 200;
 const WTX_MAX_VALUE: u8 = 1;
 
+/// Number of logical channels multiplexed by the NAD bytes
+pub const CHANNELS_MAX: usize = 4;
+
 /// Maximum for extended APDU response
 const RECV_MAX: usize = 65536 + 2;
 
-const REQUEST_RESYNC: u8 = 0x00;
-const REQUEST_IFS: u8 = 0x01;
-const REQUEST_ABORT: u8 = 0x02;
-const REQUEST_WTX: u8 = 0x03;
-const REQUEST_RESET: u8 = 0x05;
+/// Protocol Parameter Selection result
+#[derive(Debug, PartialEq)]
+pub struct PpsResult {
+    /// Proposed parameters are accepted by the card
+    pub accepted: bool,
+
+    /// Negotiated Fi and Di parameters, PPS1 byte
+    pub fi_di: Option<u8>,
+}
+
+/// Cause of the last failed attempt when retries are exhausted
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RetryCause {
+    /// Received block error detection code mismatch
+    BadCrc,
 
-pub enum ChkAlgo {
-    Lrc,
+    /// No block received within the waiting time
+    Timeout,
+
+    /// Card kept rejecting the sent block with R-blocks
+    CardNak,
+
+    /// Unexpected block received in response to a request
+    BadBlock,
+}
+
+impl fmt::Display for RetryCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::BadCrc => "bad checksum",
+            Self::Timeout => "timeout",
+            Self::CardNak => "rejected by the card",
+            Self::BadBlock => "unexpected block",
+        })
+    }
 }
 
-#[derive(PartialEq)]
-enum Block {
-    I,
-    R,
-    S,
+impl RetryCause {
+    fn of<E>(err: &Error<E>) -> Option<Self> {
+        match err {
+            Error::BadCrc(_, _) | Error::BadCrc16(_, _) | Error::StateBadCrc => Some(Self::BadCrc),
+            Error::Timeout(_)
+            | Error::CwtTimeout { .. }
+            | Error::ReadHdrTimeout(_, _)
+            | Error::ReadDataTimeout(_, _) => Some(Self::Timeout),
+            Error::RbTimeout | Error::PrevBlkCrc | Error::RbResync => Some(Self::CardNak),
+            Error::Ebade(_) => Some(Self::BadBlock),
+            _ => None,
+        }
+    }
+}
+
+/// Protocol statistics counters, kept until `reset_stats()`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// Transmitted I-blocks
+    pub tx_iblocks: u32,
+
+    /// Transmitted R-blocks
+    pub tx_rblocks: u32,
+
+    /// Transmitted S-blocks
+    pub tx_sblocks: u32,
+
+    /// Received I-blocks
+    pub rx_iblocks: u32,
+
+    /// Received R-blocks
+    pub rx_rblocks: u32,
+
+    /// Received S-blocks
+    pub rx_sblocks: u32,
+
+    /// Received blocks with error detection code mismatch
+    pub crc_errors: u32,
+
+    /// Blocks not received within the waiting time
+    pub timeouts: u32,
+
+    /// Granted S(WTX request) blocks
+    pub wtx_granted: u32,
+
+    /// Completed S(RESYNCH) exchanges
+    pub resyncs: u32,
+
+    /// I-blocks sent again before being acknowledged
+    pub retransmissions: u32,
+}
+
+/// Protocol state snapshot left by the last exchange
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProtocolState {
+    /// The exchange was halted by an unrecoverable protocol error
+    /// instead of retrying
+    pub halted: bool,
+
+    /// The exchange was aborted by an S(ABORT) exchange
+    pub aborted: bool,
+
+    /// Send sequence number N(S) of the next I-block
+    pub send_seq: u8,
+
+    /// Expected sequence number N(S) of the next received I-block
+    pub recv_seq: u8,
+}
+
+/// Block transfer direction of the raw block trace
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// Block written to the card
+    Tx,
+
+    /// Block received from the card
+    Rx,
 }
 
 #[derive(Default)]
 struct State {
     halt: bool,
+    halted: bool,
     request: bool,
     reqresp: bool,
     badcrc: bool,
     timeout: bool,
     aborted: bool,
+    chaining: bool,
+    host_abort: bool,
+    abort_pending: bool,
+    err_pending: bool,
+    ifsd_req: Option<u8>,
 }
 
 struct Ifs {
@@ -57,12 +181,20 @@ impl Default for Ifs {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 struct Nad {
     card: u8,
     dev: u8,
 }
 
+/// NAD bytes and sequence numbers of an inactive logical channel
+#[derive(Clone, Copy)]
+struct Channel {
+    nad: Nad,
+    send_next: u8,
+    recv_next: u8,
+}
+
 struct Wtx {
     wtx: u8,
     rounds: i32,
@@ -99,57 +231,539 @@ struct Atr {
     len: usize,
 }
 
+/// Send window over the command APDU, which is passed to `process()`
 #[derive(Default)]
-struct Snd<'a> {
-    buf: &'a [u8],
+struct Snd {
+    off: usize,
     len: usize,
     next: u8,
+    open: bool,
+    unacked: bool,
 }
 
+/// Receive window over the response APDU, which is passed to `process()`
 #[derive(Default)]
-struct Recv<'a> {
-    buf: &'a mut [u8],
+struct Recv {
     len: usize,
     next: u8,
     size: usize,
+    discard: bool,
+}
+
+/// Human readable trace of the exchanged blocks, received bytes are
+/// gathered until a whole block is available
+struct BlockTrace<'t, TR> {
+    out: &'t mut TR,
+    card_nad: u8,
+    chk_len: usize,
+    buf: [u8; BUF_SIZE],
+    n: usize,
 }
 
-pub struct T1Proto<'a, E> {
+impl<TR: fmt::Write> BlockTrace<'_, TR> {
+    fn sent(&mut self, block: &[u8]) {
+        // Tracing is best effort, the exchange goes on if it fails
+        let _ = write_block(self.out, "→", block);
+    }
+
+    fn received(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if self.n == 0 && b != self.card_nad {
+                continue;
+            }
+            self.buf[self.n] = b;
+            self.n += 1;
+
+            if self.n >= 3 && self.n == 3 + usize::from(self.buf[2]) + self.chk_len {
+                let _ = write_block(self.out, "←", &self.buf[..self.n]);
+                self.n = 0;
+            }
+        }
+    }
+}
+
+/// Write block description and its information field as hex
+fn write_block<TR: fmt::Write>(out: &mut TR, dir: &str, block: &[u8]) -> fmt::Result {
+    let [_, pcb, len, ..] = *block else {
+        return writeln!(out, "{dir} ?[len={}]", block.len());
+    };
+
+    match block_kind(pcb) {
+        BlockKind::I => write!(
+            out,
+            "{dir} I[seq={}, len={len}, M={}]",
+            (pcb >> 6) & 1,
+            u8::from(is_chaining(pcb))
+        )?,
+        BlockKind::R => write!(out, "{dir} R[seq={}, err={}]", (pcb >> 4) & 1, pcb & 0x0f)?,
+        BlockKind::S => write!(out, "{dir} S[pcb={pcb:02x}, len={len}]")?,
+    }
+
+    let data = &block[3..block.len().min(3 + usize::from(len))];
+    if !data.is_empty() {
+        out.write_str(": ")?;
+        for b in data {
+            write!(out, "{b:02x}")?;
+        }
+    }
+
+    out.write_char('\n')
+}
+
+/// One block exchange of `T1Iter`: NAD, PCB and LEN of the sent block
+/// and of the received one, if a whole prologue arrived
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IterEvent {
+    /// Prologue of the sent block
+    pub send: [u8; 3],
+
+    /// Prologue of the received block
+    pub recv: Option<[u8; 3]>,
+}
+
+/// Transmission exchanging one block per `next()` call, so the caller can
+/// interleave other work between the exchanges
+pub struct T1Iter<'p, 'c, E, R, W> {
+    t1: &'p mut T1Proto<E>,
+    read: R,
+    write: W,
+    capdu: &'c [u8],
+    rapdu: &'c mut [u8],
+    done: bool,
+}
+
+impl<E, R, W> T1Iter<'_, '_, E, R, W> {
+    /// Response length received so far
+    pub fn received(&self) -> usize {
+        self.t1.recv.len
+    }
+
+    /// Block exchange retries left for the current block
+    pub fn retries_remaining(&self) -> u8 {
+        self.t1.retries_remaining()
+    }
+
+    /// S(WTX request) rounds left for the current block
+    pub fn wtx_rounds_remaining(&self) -> i32 {
+        self.t1.wtx_rounds_remaining()
+    }
+}
+
+impl<E, R, W> Iterator for T1Iter<'_, '_, E, R, W>
+where
+    R: Fn(&mut [u8]) -> Result<usize, E>,
+    W: Fn(&[u8]) -> Result<usize, E>,
+{
+    type Item = Result<IterEvent, Error<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let sent = Cell::new([0; 3]);
+        let write = |b: &[u8]| {
+            if let [nad, pcb, len, ..] = *b {
+                sent.set([nad, pcb, len]);
+            }
+            (self.write)(b)
+        };
+
+        match self
+            .t1
+            .process_step(&self.read, &write, self.capdu, self.rapdu)
+        {
+            Ok(true) => {
+                let t1 = &self.t1;
+                let recv = (t1.n >= 3).then(|| [t1.buf[0], t1.buf[1], t1.buf[2]]);
+
+                Some(Ok(IterEvent {
+                    send: sent.get(),
+                    recv,
+                }))
+            }
+            Ok(false) => {
+                self.done = true;
+                self.t1.process_end().err().map(Err)
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Format block fields with labels, e.g.
+/// `NAD=51 PCB=00(I,seq0) LEN=04 DATA=80ca9f7f EDC=ff`
+#[cfg(feature = "alloc")]
+pub fn dump_block(block: &[u8]) -> String {
+    let mut out = String::new();
+    // Writing to a String does not fail
+    let _ = write_dump(&mut out, block);
+
+    out
+}
+
+#[cfg(feature = "alloc")]
+fn write_dump(out: &mut String, block: &[u8]) -> fmt::Result {
+    use fmt::Write;
+
+    let [nad, pcb, len, ..] = *block else {
+        out.write_str("?")?;
+        return write_hex(out, block);
+    };
+
+    write!(out, "NAD={nad:02x} PCB={pcb:02x}(")?;
+    match block_kind(pcb) {
+        BlockKind::I => {
+            write!(out, "I,seq{}", (pcb >> 6) & 1)?;
+            if is_chaining(pcb) {
+                out.write_str(",M")?;
+            }
+        }
+        BlockKind::R => write!(out, "R,seq{},err{}", (pcb >> 4) & 1, pcb & 0x0f)?,
+        BlockKind::S => {
+            let block::SBlock { req, dir } = block::SBlock::from_pcb(pcb);
+            let dir = match dir {
+                SBlockDir::Request => "req",
+                SBlockDir::Response => "resp",
+            };
+            write!(out, "S,{req} {dir}")?;
+        }
+    }
+    write!(out, ") LEN={len:02x}")?;
+
+    let end = block.len().min(3 + usize::from(len));
+    if end > 3 {
+        out.write_str(" DATA=")?;
+        write_hex(out, &block[3..end])?;
+    }
+    if block.len() > end {
+        out.write_str(" EDC=")?;
+        write_hex(out, &block[end..])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+fn write_hex(out: &mut String, bytes: &[u8]) -> fmt::Result {
+    use fmt::Write;
+
+    bytes.iter().try_for_each(|b| write!(out, "{b:02x}"))
+}
+
+/// ISO/IEC 7816 T=1 block engine. The connection interface is accessed
+/// by the `read` and `write` closures passed to each exchange.
+//
+// Auto Send and Sync: the APDU buffers are borrowed per call and never
+// stored, the callbacks are plain `fn` pointers and the abort request and
+// flag are atomics, so only `E` decides, no unsafe impl is needed.
+pub struct T1Proto<E> {
     state: State,
     ifs: Ifs,
     nad: Nad,
+    channel: u8,
+    channels: [Option<Channel>; CHANNELS_MAX],
     bwt: u32,
+    nad_timeout: Option<u32>,
+    total_timeout: Option<u32>,
+    total_start: Option<u32>,
+    total_slept: u32,
+    noise_max: Option<usize>,
+    cwt: Option<u32>,
     chk_algo: ChkAlgo,
     retries: u8,
-    request: u8,
+    request: Option<SBlockRequest>,
     wtx: Wtx,
+    wtx_max_value: u8,
+    max_wtx_rounds: i32,
     need: Need,
     atr: Atr,
-    send: Snd<'a>,
-    recv: Recv<'a>,
+    send: Snd,
+    recv: Recv,
     recv_max: usize,
+    buf_size: usize,
     recv_size: usize,
+    overflow: bool,
+    truncated: bool,
     buf: [u8; BUF_SIZE],
     n: usize,
-    sleep_cb: fn(u32),
+    sleep_cb: Option<fn(u32)>,
+    now_cb: Option<fn() -> u32>,
+    sim_time: Option<&'static SimulatedTime>,
+    yield_cb: fn(),
+    wait_progress_cb: Option<fn(u32)>,
+    #[cfg(feature = "trace")]
+    state_cb: Option<fn(&str)>,
+    trace_cb: Option<fn(Direction, &[u8])>,
     soft_reset: bool,
+    lenient: bool,
+    any_card_nad: bool,
+    block_read: bool,
+    stats: Stats,
+    abort_requested: AtomicBool,
+    abort_flag: Option<&'static AtomicBool>,
     err: Result<(), Error<E>>,
 }
 
-impl<'a, E> T1Proto<'a, E> {
+impl<E> T1Proto<E> {
+    /// Set NAD bytes of the card and the device for the blocks of the
+    /// basic logical channel
     pub fn set_nad(&mut self, card_nad: u8, dev_nad: u8) {
         self.nad.card = card_nad;
         self.nad.dev = dev_nad;
     }
 
-    pub fn set_sleep_cb(&mut self, cb: fn(u32)) {
+    /// Set NAD bytes of an additional logical channel from 1 to 3.
+    /// Returns false if the channel is out of range.
+    pub fn set_channel_nad(&mut self, channel: u8, card_nad: u8, dev_nad: u8) -> bool {
+        let Some(slot) = self.channels.get_mut(usize::from(channel)) else {
+            return false;
+        };
+        *slot = Some(Channel {
+            nad: Nad {
+                card: card_nad,
+                dev: dev_nad,
+            },
+            send_next: 0,
+            recv_next: 0,
+        });
+
+        true
+    }
+
+    /// Switch to another logical channel, keeping the NAD bytes and the
+    /// sequence numbers of the current one. Returns false if the channel
+    /// is not set.
+    pub fn select_channel(&mut self, channel: u8) -> bool {
+        if channel == self.channel {
+            return true;
+        }
+        let Some(Some(next)) = self.channels.get(usize::from(channel)).copied() else {
+            return false;
+        };
+
+        self.channels[usize::from(self.channel)] = Some(Channel {
+            nad: self.nad,
+            send_next: self.send.next,
+            recv_next: self.recv.next,
+        });
+        self.nad = next.nad;
+        self.send.next = next.send_next;
+        self.recv.next = next.recv_next;
+        self.channel = channel;
+
+        true
+    }
+
+    /// Set timeout in milliseconds for receiving the card NAD byte, `None`
+    /// waits up to the block waiting time
+    pub fn set_nad_timeout(&mut self, timeout: Option<u32>) {
+        self.nad_timeout = timeout;
+    }
+
+    /// Set upper bound in milliseconds of a whole exchange, `None` for no
+    /// bound
+    pub fn set_total_timeout(&mut self, timeout: Option<u32>) {
+        self.total_timeout = timeout;
+    }
+
+    /// Set character waiting time in milliseconds within a received block
+    pub fn set_cwt(&mut self, cwt: Option<u32>) {
+        self.cwt = cwt;
+    }
+
+    /// Set maximum number of noise bytes discarded before the NAD byte
+    pub fn set_noise_max(&mut self, max: Option<usize>) {
+        self.noise_max = max;
+    }
+
+    /// Set block error detection code, LRC by default
+    pub fn set_chk_algo(&mut self, algo: ChkAlgo) {
+        self.chk_algo = algo;
+    }
+
+    /// Set block buffer size, limited to the maximum block of 260 bytes
+    pub fn set_buf_size(&mut self, size: usize) {
+        self.buf_size = size.min(BUF_SIZE);
+    }
+
+    /// Set maximum response APDU length
+    pub fn set_recv_max(&mut self, max: usize) {
+        self.recv_max = max;
+    }
+
+    /// Set timer sleeping callback taking milliseconds
+    pub fn set_sleep_cb(&mut self, cb: Option<fn(u32)>) {
         self.sleep_cb = cb;
     }
 
+    /// Set monotonic clock callback returning milliseconds
+    pub fn set_now_cb(&mut self, cb: Option<fn() -> u32>) {
+        self.now_cb = cb;
+    }
+
+    /// Measure the timeouts in simulated time, advanced by the protocol
+    /// sleeps instead of the sleeping and clock callbacks
+    pub fn set_simulated_time(&mut self, time: Option<&'static SimulatedTime>) {
+        self.sim_time = time;
+    }
+
+    /// Set the flag requesting the current exchange to be aborted by
+    /// S(ABORT) exchange. Set from another context, e.g. an interrupt, while
+    /// the exchange runs; it is cleared when an exchange begins, so a stale
+    /// signal does not abort the next one. The flag is `'static` to be
+    /// reachable from the contexts not holding the protocol engine.
+    pub fn set_abort_flag(&mut self, flag: Option<&'static AtomicBool>) {
+        self.abort_flag = flag;
+    }
+
+    /// Set callback called while waiting for the card, e.g. to yield to a
+    /// cooperative scheduler
+    pub fn set_yield_cb(&mut self, cb: fn()) {
+        self.yield_cb = cb;
+    }
+
+    /// Set callback reporting the milliseconds left while waiting for the
+    /// card response
+    pub fn set_wait_progress_cb(&mut self, cb: Option<fn(u32)>) {
+        self.wait_progress_cb = cb;
+    }
+
+    /// Set maximum accepted waiting time extension multiplier, zero
+    /// accepts any
+    pub fn set_wtx_max_value(&mut self, v: u8) {
+        self.wtx_max_value = v;
+    }
+
+    /// Set maximum number of consecutive waiting time extensions, zero
+    /// allows any
+    pub fn set_wtx_max_rounds(&mut self, rounds: i32) {
+        self.max_wtx_rounds = rounds;
+    }
+
+    /// Enable soft reset by S(RESET) exchange. Without it `reset()` does
+    /// nothing and no ATR is fetched.
     pub fn set_soft_reset(&mut self, enabled: bool) {
         self.soft_reset = enabled;
     }
 
+    /// Set protocol state transition tracing callback
+    #[cfg(feature = "trace")]
+    pub fn set_state_cb(&mut self, cb: Option<fn(&str)>) {
+        self.state_cb = cb;
+    }
+
+    /// Set callback receiving every sent and received block
+    pub fn set_trace_cb(&mut self, cb: Option<fn(Direction, &[u8])>) {
+        self.trace_cb = cb;
+    }
+
+    /// Accept blocks with reserved PCB bits set or unknown codes
+    pub fn set_lenient(&mut self, enabled: bool) {
+        self.lenient = enabled;
+    }
+
+    /// Accept blocks with any source NAD instead of the configured card
+    /// NAD. The idle line bytes 0x00 and 0xff are still skipped while
+    /// waiting for the block.
+    pub fn set_any_card_nad(&mut self, enabled: bool) {
+        self.any_card_nad = enabled;
+    }
+
+    /// Read each block with a single `read` call instead of the NAD,
+    /// header and data parts
+    pub fn set_block_read(&mut self, enabled: bool) {
+        self.block_read = enabled;
+    }
+
+    /// Check if the last response did not fit into the receive buffer
+    pub fn response_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Protocol statistics counters
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Clear protocol statistics counters
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Send and receive sequence numbers of the next I-blocks
+    pub fn sequence_numbers(&self) -> (u8, u8) {
+        (self.send.next, self.recv.next)
+    }
+
+    /// Card information field size IFSC, the maximum I-block length sent
+    /// to the card
+    pub fn ifs_card(&self) -> u8 {
+        self.ifs.card
+    }
+
+    /// Device information field size IFSD, the maximum I-block length
+    /// accepted from the card
+    pub fn ifs_dev(&self) -> u8 {
+        self.ifs.dev
+    }
+
+    /// Block exchange retries left for the current block. Each failed
+    /// block exchange takes one retry and a valid block restores all 3,
+    /// so the value is 3 after a successful operation and 0 after one
+    /// failed with exhausted retries.
+    pub fn retries_remaining(&self) -> u8 {
+        self.retries
+    }
+
+    /// S(WTX request) rounds left before the exchange fails with
+    /// `NoRoundsLeft`. Each granted request takes one round and the next
+    /// I-block or R-block restores all of them.
+    pub fn wtx_rounds_remaining(&self) -> i32 {
+        self.wtx.rounds
+    }
+
+    /// Last protocol error recovered during the last exchange, e.g. a bad
+    /// checksum followed by a successful retransmission
+    pub fn recovered_error(&self) -> Option<&Error<E>> {
+        match &self.err {
+            Err(e) if !self.state.err_pending => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Protocol state snapshot left by the last exchange
+    pub fn protocol_state(&self) -> ProtocolState {
+        ProtocolState {
+            halted: self.state.halted,
+            aborted: self.state.aborted,
+            send_seq: self.send.next,
+            recv_seq: self.recv.next,
+        }
+    }
+
+    /// Check if a reset is required before the next exchange
+    pub fn needs_reset(&self) -> bool {
+        self.need.reset
+    }
+
+    /// Require a reset before the next exchange
+    pub fn set_need_reset(&mut self) {
+        self.need.reset = true;
+    }
+
+    /// Forget the Answer To Reset, so it is fetched again by the next
+    /// exchange
+    pub fn clear_atr(&mut self) {
+        self.atr.len = 0;
+        self.need.reset = true;
+    }
+
+    /// Soft reset by S(RESET) exchange fetching the Answer To Reset,
+    /// followed by the IFS negotiation
     pub fn reset<R, W>(&mut self, read: R, write: W) -> Result<(), Error<E>>
     where
         R: Fn(&mut [u8]) -> Result<usize, E>,
@@ -162,9 +776,121 @@ impl<'a, E> T1Proto<'a, E> {
         self.clear_states();
         self.need.reset = true;
 
-        self.process(read, write)
+        self.process(read, write, &[], &mut [])
+    }
+
+    /// Run only the IFS negotiation and get the negotiated IFSD
+    pub fn sync_ifs<R, W>(&mut self, read: R, write: W) -> Result<u8, Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        self.clear_states();
+        self.need.ifsd_sync = true;
+
+        self.process(read, write, &[], &mut [])?;
+
+        Ok(self.ifs.dev)
     }
 
+    /// Resynchronize by S(RESYNCH) exchange without a reset
+    pub fn resync<R, W>(&mut self, read: R, write: W) -> Result<(), Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        self.clear_states();
+        self.need.resync = true;
+
+        self.process(read, write, &[], &mut [])
+    }
+
+    /// Negotiate the device information field size IFSD by S(IFS)
+    /// exchange, the card may accept a smaller value
+    pub fn negotiate_ifsd<R, W>(&mut self, ifsd: u8, read: R, write: W) -> Result<u8, Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        if ifsd == 0 || ifsd > self.ifsd_max() {
+            return Err(Error::InvalidIfsd(ifsd));
+        }
+
+        self.clear_states();
+        self.need.ifsd_sync = true;
+        self.state.ifsd_req = Some(ifsd);
+
+        self.process(read, write, &[], &mut [])?;
+
+        Ok(self.ifs.dev)
+    }
+
+    /// Negotiate protocol parameters (PPS) with the card after ATR
+    pub fn negotiate_pps<R, W>(
+        &mut self,
+        pps0: u8,
+        pps1: Option<u8>,
+        read: R,
+        write: W,
+    ) -> Result<PpsResult, Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        let mut req = [0xff, pps0 & 0x0f, 0, 0];
+        let mut n = 2;
+        if let Some(pps1) = pps1 {
+            req[1] |= 0x10;
+            req[n] = pps1;
+            n += 1;
+        }
+        req[n] = req[..n].iter().fold(0, |c, b| c ^ b);
+        n += 1;
+
+        let len = write(&req[..n]).map_err(Error::Write)?;
+        if len != n {
+            return Err(Error::WriteLen(n, len));
+        }
+
+        let mut resp = [0u8; 6];
+        let len = read(&mut resp[..2]).map_err(Error::ReadHdr)?;
+        if len != 2 {
+            return Err(Error::ReadHdrLen(len, 2));
+        }
+        if resp[0] != 0xff {
+            return Err(Error::PpsRejected);
+        }
+
+        // Optional PPS1, PPS2, PPS3 bytes and PCK
+        let n = 2 + (resp[1] >> 4 & 7).count_ones() as usize + 1;
+        let len = read(&mut resp[2..n]).map_err(Error::ReadData)?;
+        if len != n - 2 {
+            return Err(Error::ReadDataLen(len, n - 2));
+        }
+
+        if resp[..n].iter().fold(0, |c, b| c ^ b) != 0 || resp[1] & 0x0f != req[1] & 0x0f {
+            return Err(Error::PpsMismatch);
+        }
+
+        match (pps1, resp[1] & 0x10 != 0) {
+            (Some(pps1), true) if resp[2] != pps1 => Err(Error::PpsMismatch),
+            (None, true) => Err(Error::PpsMismatch),
+            (Some(pps1), true) => Ok(PpsResult {
+                accepted: true,
+                fi_di: Some(pps1),
+            }),
+            (Some(_), false) => Ok(PpsResult {
+                accepted: false,
+                fi_di: None,
+            }),
+            (None, false) => Ok(PpsResult {
+                accepted: true,
+                fi_di: None,
+            }),
+        }
+    }
+
+    /// Get the Answer To Reset, resetting the card if required
     pub fn atr<R, W>(&mut self, read: R, write: W) -> Result<&[u8], Error<E>>
     where
         R: Fn(&mut [u8]) -> Result<usize, E>,
@@ -177,39 +903,180 @@ impl<'a, E> T1Proto<'a, E> {
         Ok(&self.atr.buf[..self.atr.len])
     }
 
+    /// Get block waiting time in milliseconds
+    pub fn bwt(&self) -> u32 {
+        self.bwt
+    }
+
+    /// Set the block waiting time from BWI of the last Answer To Reset
+    pub fn apply_atr_timings(&mut self) {
+        let info = atr::parse_atr(self.last_atr());
+        self.bwt = info.bwt().unwrap_or(BWT_DEFAULT);
+    }
+
+    /// Answer To Reset of the last reset, without the TS byte
+    pub fn last_atr(&self) -> &[u8] {
+        &self.atr.buf[..self.atr.len]
+    }
+
+    /// Transmit command APDU and receive response APDU into `rapdu`,
+    /// returning the response length
     pub fn transmit<R, W>(
         &mut self,
-        capdu: &'a [u8],
-        rapdu: &'a mut [u8],
+        capdu: &[u8],
+        rapdu: &mut [u8],
         read: R,
         write: W,
-    ) -> Result<&[u8], Error<E>>
+    ) -> Result<usize, Error<E>>
     where
         R: Fn(&mut [u8]) -> Result<usize, E>,
         W: Fn(&[u8]) -> Result<usize, E>,
     {
+        self.transmit_begin(capdu.len(), rapdu.len());
+        while self.process_step(&read, &write, capdu, rapdu)? {}
+        self.process_end()?;
+
+        Ok(self.recv.len)
+    }
+
+    /// Begin transmission driven block by block by the returned iterator
+    pub fn transmit_iter<'p, 'c, R, W>(
+        &'p mut self,
+        capdu: &'c [u8],
+        rapdu: &'c mut [u8],
+        read: R,
+        write: W,
+    ) -> T1Iter<'p, 'c, E, R, W>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        self.transmit_begin(capdu.len(), rapdu.len());
+
+        T1Iter {
+            t1: self,
+            read,
+            write,
+            capdu,
+            rapdu,
+            done: false,
+        }
+    }
+
+    /// Transmit command APDU writing a human readable line for each sent
+    /// and received block to `trace`
+    pub fn transmit_traced<R, W, TR>(
+        &mut self,
+        capdu: &[u8],
+        rapdu: &mut [u8],
+        read: R,
+        write: W,
+        trace: &mut TR,
+    ) -> Result<usize, Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+        TR: fmt::Write,
+    {
+        let trace = RefCell::new(BlockTrace {
+            out: trace,
+            card_nad: self.nad.card,
+            chk_len: self.chk_algo_len(),
+            buf: [0; BUF_SIZE],
+            n: 0,
+        });
+        let read = |b: &mut [u8]| {
+            let n = read(b)?;
+            trace.borrow_mut().received(&b[..n.min(b.len())]);
+            Ok(n)
+        };
+        let write = |b: &[u8]| {
+            trace.borrow_mut().sent(b);
+            write(b)
+        };
+
+        self.transmit(capdu, rapdu, read, write)
+    }
+
+    pub(crate) fn transmit_begin(&mut self, capdu_len: usize, rapdu_len: usize) {
         self.clear_states();
 
-        self.send.buf = capdu;
-        self.send.len = capdu.len();
-        self.recv.buf = rapdu;
+        self.send.len = capdu_len;
+        self.send.open = true;
+        self.recv.len = 0;
+        self.recv.size = rapdu_len;
+
+        self.process_begin();
+    }
+
+    /// Begin transmission without a response buffer, the data of each
+    /// received I-block is only available from `block_data()`
+    pub(crate) fn transmit_begin_discard(&mut self, capdu_len: usize) {
+        self.transmit_begin(capdu_len, isize::MAX as usize);
+        self.recv.discard = true;
+    }
+
+    /// Data field of the last received block
+    pub(crate) fn block_data(&self) -> &[u8] {
+        &self.buf[3..3 + usize::from(self.buf[2])]
+    }
+
+    /// Take the received bytes out of the receive window, the following
+    /// bytes are received from the start of the remaining buffer
+    pub(crate) fn take_received(&mut self) -> usize {
+        let n = self.recv.len;
+        self.recv.size -= n;
         self.recv.len = 0;
-        self.recv.size = self.recv.buf.len();
 
-        self.process(read, write)?;
+        n
+    }
 
-        Ok(&self.recv.buf[..self.recv.len])
+    /// Request the exchange in progress, or the next one, to be aborted by
+    /// S(ABORT) exchange, e.g. between the steps of `transmit_iter()`.
+    /// Unlike the flag of `set_abort_flag()`, the request is kept until an
+    /// exchange takes it.
+    pub fn request_abort(&self) {
+        self.abort_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Abort the chained transmission left in progress by S(ABORT)
+    /// exchange, does nothing without one
+    pub fn abort<R, W>(&mut self, read: R, write: W) -> Result<(), Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        if !self.state.chaining {
+            return Ok(());
+        }
+
+        self.clear_states();
+        self.close_send_window();
+        self.close_recv_window();
+        self.state.request = true;
+        self.request = Some(SBlockRequest::Abort);
+
+        self.process(read, write, &[], &mut [])
     }
 
     fn clear_states(&mut self) {
         self.state = State::default();
-        self.wtx = Wtx::default();
+        self.wtx = Wtx {
+            rounds: self.max_wtx_rounds,
+            ..Wtx::default()
+        };
         self.retries = MAX_RETRIES;
-        self.request = 0xff;
+        self.request = None;
+        self.send.off = 0;
         self.send.len = 0;
+        self.send.open = false;
+        self.send.unacked = false;
         self.recv.len = 0;
         self.recv.size = 0;
+        self.recv.discard = false;
         self.recv_size = 0;
+        self.overflow = false;
+        self.truncated = false;
         self.n = 0;
         self.err = Ok(());
     }
@@ -217,126 +1084,97 @@ impl<'a, E> T1Proto<'a, E> {
     fn process_init(&mut self) {
         if self.need.reset {
             self.state.request = true;
-            self.request = REQUEST_RESET;
+            self.request = Some(SBlockRequest::Reset);
         } else if self.need.resync {
             self.state.request = true;
-            self.request = REQUEST_RESYNC;
+            self.request = Some(SBlockRequest::Resynch);
         } else if self.need.ifsd_sync {
             self.state.request = true;
-            self.request = REQUEST_IFS;
-            self.ifs.dev = 254;
-        }
-    }
-
-    fn lrc8(&mut self, n: usize) -> u8 {
-        let mut c = 0u8;
-
-        for it in self.buf[..n].iter() {
-            c ^= it;
+            self.request = Some(SBlockRequest::Ifs);
+            self.ifs.dev = self.state.ifsd_req.unwrap_or(self.ifsd_max());
         }
-
-        c
     }
 
-    fn append_lrc8(&mut self, n: usize) -> usize {
-        self.buf[n] = self.lrc8(n);
-
-        n + 1
+    /// Encode the block into the block buffer, which always has room for
+    /// the information field of up to IFS bytes
+    fn encode(&mut self, pcb: u8, inf: &[u8]) {
+        let n = block::encode(&mut self.buf, self.nad.dev, pcb, inf, self.chk_algo);
+        self.n = n.expect("block fits the block buffer");
     }
 
-    fn do_chk(&mut self) {
-        let n = 3 + usize::from(self.buf[2]);
-
-        self.n = match self.chk_algo {
-            ChkAlgo::Lrc => self.append_lrc8(n),
+    fn write_s_block(&mut self, req: SBlockRequest, dir: SBlockDir) {
+        let inf = match (req, dir) {
+            (SBlockRequest::Ifs, SBlockDir::Request) => Some(self.ifs.dev),
+            (SBlockRequest::Ifs, SBlockDir::Response) => Some(self.ifs.card),
+            (SBlockRequest::Wtx, _) => Some(self.wtx.wtx),
+            _ => None,
         };
-    }
 
-    fn write_request(&mut self, mask: u8) {
-        let mut request = self.request | mask;
-
-        self.buf[0] = self.nad.dev;
-        self.buf[1] = 0xc0 | request;
-
-        request &= 0x1f;
-        if request == REQUEST_IFS {
-            self.buf[2] = 1;
-            if self.buf[1] & 0x20 != 0 {
-                self.buf[3] = self.ifs.card;
-            } else {
-                self.buf[3] = self.ifs.dev;
-            }
-        } else if request == REQUEST_WTX {
-            self.buf[2] = 1;
-            self.buf[3] = self.wtx.wtx;
-        } else {
-            self.buf[2] = 0;
-        }
-
-        self.do_chk();
+        self.encode(block::sblock_pcb(req, dir), inf.as_slice());
     }
 
     fn write_rblock(&mut self, n: u8) {
-        self.buf[0] = self.nad.dev;
-        self.buf[1] = 0x80 | (n & 3);
-        if self.recv.next != 0 {
-            self.buf[1] |= 0x10;
-        }
-        self.buf[2] = 0;
-
-        self.do_chk();
+        self.encode(block::rblock_pcb(self.recv.next, n), &[]);
     }
 
-    fn write_iblock(&mut self) {
-        let mut n = self.send_window_size();
-        let mut pcb: u8;
-
-        if n > self.ifs.card.into() {
-            n = self.ifs.card.into();
-            pcb = 0x20;
-        } else {
-            pcb = 0;
-        }
+    fn write_iblock(&mut self, capdu: &[u8]) {
+        let n = self.send_window_size().min(self.ifs.card.into());
+        let more = n < self.send_window_size();
 
-        if self.send.next != 0 {
-            pcb |= 0x40;
+        self.state.chaining |= more;
+        if self.send.unacked {
+            self.stats.retransmissions += 1;
         }
+        self.send.unacked = true;
 
-        self.buf[0] = self.nad.dev;
-        self.buf[1] = pcb;
-        self.buf[2] = n.try_into().unwrap();
-        self.buf[3..n + 3].copy_from_slice(&self.send.buf[..n]);
-
-        self.do_chk();
+        let off = self.send.off;
+        let pcb = block::iblock_pcb(self.send.next, more);
+        self.encode(pcb, &capdu[off..off + n]);
     }
 
-    fn request_init(&mut self) -> Result<(), Error<E>> {
-        if self.state.request {
-            self.write_request(0x00);
-        } else if self.state.reqresp {
-            self.write_request(0x20);
+    fn request_init(&mut self, capdu: &[u8]) -> Result<(), Error<E>> {
+        if let (true, Some(req)) = (self.state.request, self.request) {
+            self.write_s_block(req, SBlockDir::Request);
+        } else if let (true, Some(req)) = (self.state.reqresp, self.request) {
+            self.write_s_block(req, SBlockDir::Response);
             self.state.reqresp = false;
         } else if self.state.badcrc {
             self.write_rblock(1);
         } else if self.state.timeout {
             self.write_rblock(0);
-        } else if self.send_window_size() != 0 {
-            self.write_iblock();
+        } else if self.send.open {
+            self.write_iblock(capdu);
         } else if self.state.aborted {
             return Err(Error::Aborted);
-        } else if self.recv_window_size() > 0 {
+        } else if self.recv.size > 0 {
+            // Request the response I-block
             self.write_rblock(0);
         } else {
-            return Err(Error::NoRespIBlock);
+            return Err(Error::NoRespIBlock(self.buf[1]));
         }
 
         Ok(())
     }
 
+    /// Largest information field the block buffer can receive
+    fn ifsd_max(&self) -> u8 {
+        let max = self.buf_size.saturating_sub(3 + self.chk_algo_len());
+        max.min(254).try_into().unwrap()
+    }
+
     fn chk_algo_len(&self) -> usize {
-        match self.chk_algo {
-            ChkAlgo::Lrc => 1,
-        }
+        self.chk_algo.edc_len()
+    }
+
+    /// Block waiting time extended by the requested WTX multiplier,
+    /// saturated to `u32::MAX` instead of wrapping around
+    fn block_timeout(&self) -> u32 {
+        let wtx = match self.wtx.wtx {
+            0 => 1,
+            wtx => u32::from(wtx),
+        };
+
+        self.bwt.saturating_mul(wtx)
     }
 
     fn block_recv<R>(&mut self, read: R) -> Result<(), Error<E>>
@@ -345,18 +1183,27 @@ impl<'a, E> T1Proto<'a, E> {
     {
         self.n = 0;
 
-        let bwt = self.bwt
-            * if self.wtx.wtx != 0 {
-                self.wtx.wtx.into()
-            } else {
-                1
-            };
+        let bwt = self.block_timeout();
         self.wtx.wtx = 1;
 
-        let mut clock = Clock::new(bwt, self.sleep_cb);
+        if self.block_read {
+            return self.block_recv_whole(&read);
+        }
+
+        let mut clock = self.clock(bwt);
+
+        let mut discarded = 0usize;
 
         loop {
-            clock.sleep(2);
+            (self.yield_cb)();
+            if self.take_abort() {
+                return Err(Error::AbortRequested);
+            }
+            self.sleep(&mut clock, 2);
+            self.check_total_timeout()?;
+            if let Some(cb) = self.wait_progress_cb {
+                cb(clock.remaining());
+            }
 
             let n = read(&mut self.buf[..1]).map_err(Error::ReadNad)?;
             if n != 1 {
@@ -364,49 +1211,172 @@ impl<'a, E> T1Proto<'a, E> {
             }
             self.n = n;
 
-            if self.buf[0] == self.nad.card {
+            if self.is_card_nad(self.buf[0]) {
                 break;
             }
 
+            discarded += 1;
+            if self.noise_max.is_some_and(|max| discarded > max) {
+                return Err(Error::NoSyncByte(discarded));
+            }
+
             if clock.timeout() {
                 return Err(Error::Timeout(bwt));
             }
+
+            if self.nad_timeout.is_some_and(|t| clock.elapsed() > t) {
+                return Err(Error::NoCardResponse);
+            }
         }
 
+        // Partial reads are retried up to the rest of the waiting time
+        let wait = clock.remaining();
         let mut max = 2 + self.chk_algo_len();
-        let n = read(&mut self.buf[self.n..self.n + max]).map_err(Error::ReadHdr)?;
-        if n != max {
-            return Err(Error::ReadHdrLen(n, max));
+        self.read_chars(&read, max, wait, Error::ReadHdr, Error::ReadHdrTimeout)?;
+
+        let len = usize::from(self.buf[2]);
+        max += len;
+        if max + 1 > self.buf_size {
+            return Err(Error::RecvLen(max, len));
+        }
+
+        if len != 0 {
+            self.read_chars(&read, len, wait, Error::ReadData, Error::ReadDataTimeout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the whole block with a single driver call
+    fn block_recv_whole<R>(&mut self, read: R) -> Result<(), Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+    {
+        (self.yield_cb)();
+        if self.take_abort() {
+            return Err(Error::AbortRequested);
+        }
+
+        let n = read(&mut self.buf[..self.buf_size]).map_err(Error::ReadNad)?;
+        self.n = n;
+
+        if n >= 3 {
+            let max = 3 + usize::from(self.buf[2]) + self.chk_algo_len();
+            if n != max {
+                return Err(Error::ReadDataLen(n, max));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read block characters, waiting for the rest of them up to the
+    /// character waiting time after each received group, or up to `wait`
+    /// milliseconds in total without one
+    fn read_chars<R>(
+        &mut self,
+        read: &R,
+        len: usize,
+        wait: u32,
+        read_err: fn(E) -> Error<E>,
+        timeout_err: fn(usize, usize) -> Error<E>,
+    ) -> Result<(), Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+    {
+        let start = self.n;
+        let end = self.n + len;
+        let mut clock = self.clock(self.cwt.unwrap_or(wait));
+
+        loop {
+            let n = read(&mut self.buf[self.n..end]).map_err(read_err)?;
+            self.n += n;
+            if self.n == end {
+                return Ok(());
+            }
+
+            if let (Some(cwt), true) = (self.cwt, n != 0) {
+                clock = self.clock(cwt);
+            }
+
+            (self.yield_cb)();
+            self.sleep(&mut clock, 1);
+            self.check_total_timeout()?;
+
+            if clock.timeout() {
+                return Err(match self.cwt {
+                    Some(_) => Error::CwtTimeout {
+                        received: self.n,
+                        expected: end,
+                    },
+                    None => timeout_err(self.n - start, len),
+                });
+            }
+        }
+    }
+
+    /// Sleep on the block clock, counting the time of the whole exchange
+    fn clock(&self, timeout: u32) -> Clock {
+        match self.sim_time {
+            Some(sim) => Clock::new_simulated(timeout, sim),
+            None => Clock::new(timeout, self.sleep_cb, self.now_cb),
+        }
+    }
+
+    fn now(&self) -> Option<u32> {
+        match self.sim_time {
+            Some(sim) => Some(sim.now()),
+            None => self.now_cb.map(|now| now()),
+        }
+    }
+
+    /// Panics without a sleeping or clock callback, the timeouts would
+    /// never expire
+    fn sleep(&mut self, clock: &mut Clock, time: u32) {
+        if self.sleep_cb.is_none() && self.now_cb.is_none() && self.sim_time.is_none() {
+            panic!(
+                "sleep_cb not set: call T1Proto::set_sleep_cb or T1Proto::set_now_cb, \
+                 or TransmissionBuilder::set_sleep_cb"
+            );
         }
-        self.n += n;
+        clock.sleep(time);
+        self.total_slept = self.total_slept.saturating_add(time);
+    }
 
-        let len = usize::from(self.buf[2]);
-        max += len;
-        if max > BUF_SIZE {
-            return Err(Error::RecvLen(max, len));
+    /// Time since the exchange start, only the sleeping time is counted
+    /// without a monotonic clock
+    fn total_elapsed(&self) -> u32 {
+        match (self.now(), self.total_start) {
+            (Some(now), Some(start)) => now.wrapping_sub(start),
+            _ => self.total_slept,
         }
+    }
 
-        if len != 0 {
-            let n = read(&mut self.buf[self.n..self.n + len]).map_err(Error::ReadData)?;
-            if n != len {
-                return Err(Error::ReadDataLen(n, len));
+    fn check_total_timeout(&self) -> Result<(), Error<E>> {
+        match self.total_timeout {
+            Some(timeout) if self.total_elapsed() > timeout => {
+                Err(Error::TotalTimeout(self.total_elapsed()))
             }
-            self.n += n;
+            _ => Ok(()),
         }
-
-        Ok(())
     }
 
     fn chk_is_good(&mut self) -> Result<(), Error<E>> {
-        let n = 3 + usize::from(self.buf[2]);
+        let n = HEADER_LEN + usize::from(self.buf[2]);
 
         match self.chk_algo {
             ChkAlgo::Lrc => {
-                let chk = self.lrc8(n);
+                let chk = block::lrc8(&self.buf[..n]);
                 if chk != self.buf[n] {
                     return Err(Error::BadCrc(chk, self.buf[n]));
                 }
             }
+            ChkAlgo::Crc { little_endian } => {
+                let chk = block::crc16(&self.buf[..n], little_endian);
+                if chk != self.buf[n..n + 2] {
+                    return Err(Error::BadCrc16(chk, [self.buf[n], self.buf[n + 1]]));
+                }
+            }
         }
 
         Ok(())
@@ -417,10 +1387,13 @@ impl<'a, E> T1Proto<'a, E> {
         R: Fn(&mut [u8]) -> Result<usize, E>,
     {
         self.block_recv(read)?;
+        if let Some(cb) = self.trace_cb {
+            cb(Direction::Rx, &self.buf[..self.n]);
+        }
 
         if self.n < 3 {
             return Err(Error::ReadLen(self.n));
-        } else if self.buf[0] != self.nad.card {
+        } else if !self.is_card_nad(self.buf[0]) {
             return Err(Error::ReadNadVal(self.buf[0]));
         } else if self.buf[2] == 255 {
             return Err(Error::ReadLen255);
@@ -429,80 +1402,107 @@ impl<'a, E> T1Proto<'a, E> {
         self.chk_is_good()
     }
 
-    fn block_kind(&mut self) -> Block {
-        if self.buf[1] & 0x80 == 0 {
-            return Block::I;
-        } else if self.buf[1] & 0x40 == 0 {
-            return Block::R;
+    fn block_kind(&mut self) -> BlockKind {
+        block_kind(self.buf[1])
+    }
+
+    fn check_pcb(&mut self) -> Result<(), Error<E>> {
+        let pcb = self.buf[1];
+
+        match self.block_kind() {
+            BlockKind::I => {
+                if pcb & 0x1f != 0 {
+                    return Err(Error::PcbReserved(pcb));
+                }
+            }
+            BlockKind::R => {
+                if pcb & 0x20 != 0 {
+                    return Err(Error::PcbReserved(pcb));
+                } else if pcb & 0x0f > 3 {
+                    return Err(Error::RbUnknown(pcb));
+                }
+            }
+            BlockKind::S => {
+                if let SBlockRequest::Other(_) = SBlockRequest::from_pcb(pcb) {
+                    return Err(Error::ReqUnknown(pcb));
+                }
+            }
         }
 
-        Block::S
+        Ok(())
+    }
+
+    fn is_card_nad(&self, nad: u8) -> bool {
+        if self.any_card_nad {
+            nad != 0x00 && nad != 0xff
+        } else {
+            nad == self.nad.card
+        }
     }
 
     fn parse_atr(&mut self) {
-        let mut y = match self.atr.len {
-            0 => 0i32,
-            _ => self.atr.buf[0].into(),
-        };
-        let mut tck = y as u8;
-        let mut proto = 0;
-        let mut ifsc = -1i32;
-
-        for it in self.atr.buf.iter() {
-            let c = *it;
-            tck ^= c;
-
-            if y & 0xf0 == 0x80 {
-                y = c.into();
-                proto |= 1 << (c & 15);
-            } else if y >= 16 {
-                if ifsc < 0 && y & 0x1f == 0x11 {
-                    ifsc = c.into();
-                }
-                y &= y - 16;
-            } else {
-                y = -1;
+        let info = atr::parse_atr(&self.atr.buf[..self.atr.len]);
+
+        if info.t1() && info.tck_valid {
+            if let Some(ifsc) = info.ifsc {
+                self.ifs.card = ifsc;
             }
         }
+    }
 
-        if proto & 2 != 0 && tck == 0 {
-            self.ifs.card = ifsc as u8;
+    fn restore_defaults(&mut self) {
+        self.send.next = 0;
+        self.recv.next = 0;
+        for ch in self.channels.iter_mut().flatten() {
+            ch.send_next = 0;
+            ch.recv_next = 0;
         }
+        self.wtx = Wtx::default();
+        self.ifs = Ifs::default();
+        self.parse_atr();
     }
 
     fn parse_response(&mut self) -> Result<bool, Error<E>> {
-        let mut pcb = self.buf[1];
+        let pcb = self.buf[1];
 
         if pcb & 0x20 == 0 {
             return Ok(false);
         }
 
-        pcb &= 0x1f;
-        if pcb != self.request {
+        let Some(request) = self.request.filter(|r| r.code() == pcb & 0x1f) else {
             return Ok(false);
-        }
+        };
 
-        match pcb {
-            REQUEST_IFS => {
+        match request {
+            SBlockRequest::Ifs => {
                 self.need.ifsd_sync = false;
-                if (self.buf[2] != 1) && (self.buf[3] != self.ifs.dev) {
+                // Explicitly requested IFSD may be lowered by the card
+                let ifsd = self.buf[3];
+                let lower = self.state.ifsd_req.is_some() && (1..self.ifs.dev).contains(&ifsd);
+                if self.buf[2] != 1 || (ifsd != self.ifs.dev && !lower) {
                     return Err(Error::BadMsgIfs);
                 }
+                self.ifs.dev = ifsd;
             }
-            REQUEST_RESET => {
+            SBlockRequest::Reset => {
                 self.need.reset = false;
                 if usize::from(self.buf[2]) <= ATR_SIZE {
                     self.atr.len = self.buf[2].into();
                     self.atr.buf[..self.atr.len].copy_from_slice(&self.buf[3..self.atr.len + 3]);
-                    self.parse_atr();
+                    self.restore_defaults();
                 } else {
                     return Err(Error::BadMsgRst);
                 }
             }
-            REQUEST_RESYNC => {
+            SBlockRequest::Resynch => {
                 self.need.resync = false;
-                self.send.next = 0;
-                self.recv.next = 0;
+                self.stats.resyncs += 1;
+                self.restore_defaults();
+            }
+            SBlockRequest::Abort => {
+                self.state.chaining = false;
+                self.close_send_window();
+                self.close_recv_window();
             }
             _ => return Err(Error::NeverReq),
         }
@@ -510,10 +1510,6 @@ impl<'a, E> T1Proto<'a, E> {
         Ok(true)
     }
 
-    fn recv_window_size(&mut self) -> usize {
-        self.recv.len
-    }
-
     fn recv_window_free_size(&mut self) -> isize {
         isize::try_from(self.recv.size).unwrap() - isize::try_from(self.recv.len).unwrap()
     }
@@ -522,51 +1518,63 @@ impl<'a, E> T1Proto<'a, E> {
         self.send.len
     }
 
-    fn recv_window_append(&mut self) {
+    fn recv_window_append(&mut self, rapdu: &mut [u8]) {
         let free = self.recv_window_free_size();
         let mut n = isize::from(self.buf[2]);
 
         if n > free {
             n = free;
+            self.overflow = true;
+            self.truncated = true;
         }
 
         if n > 0 {
             let un = usize::try_from(n).unwrap();
-            self.recv.buf[self.recv.len..un].copy_from_slice(&self.buf[3..un + 3]);
+            if !self.recv.discard {
+                rapdu[self.recv.len..self.recv.len + un].copy_from_slice(&self.buf[3..un + 3]);
+            }
             self.recv.len += un;
         }
     }
 
     fn close_send_window(&mut self) {
-        self.send.buf = &[];
+        self.send.off = 0;
         self.send.len = 0;
+        self.send.open = false;
+        self.send.unacked = false;
     }
 
     fn close_recv_window(&mut self) {
-        self.recv.buf = &mut [];
         self.recv.len = 0;
         self.recv.size = 0;
     }
 
     fn ack_iblock(&mut self) {
+        // Each transmitted I-block is acknowledged once
+        if !self.send.unacked {
+            return;
+        }
+        self.send.unacked = false;
+
         let mut n = self.send_window_size();
 
         if n > self.ifs.card.into() {
             n = self.ifs.card.into();
         }
-        self.send.buf = &self.send.buf[n..];
+        self.send.off += n;
         self.send.len -= n;
+        self.send.open = self.send.len != 0;
 
         self.send.next ^= 1;
     }
 
-    fn parse_iblock(&mut self) -> usize {
+    fn parse_iblock(&mut self, rapdu: &mut [u8]) -> usize {
         let pcb = self.buf[1];
         let next = Self::zero(pcb & 0x40);
 
         if self.recv.next == next {
             self.recv.next ^= 1;
-            self.recv_window_append();
+            self.recv_window_append(rapdu);
             self.recv_size += usize::from(self.buf[2]);
         }
 
@@ -580,17 +1588,22 @@ impl<'a, E> T1Proto<'a, E> {
         match pcb & 0x2f {
             0 => {
                 if self.send.next ^ next != 0 {
+                    // Acknowledgement without an I-block waiting for it
+                    if !self.send.unacked {
+                        self.state.halt = true;
+                        return Err(Error::UnexpectedRblockSeq(pcb));
+                    }
                     self.retries = MAX_RETRIES;
                     self.ack_iblock();
                 } else {
-                    self.retries -= 1;
+                    self.retries = self.retries.saturating_sub(1);
                     if self.retries == 0 {
                         return Err(Error::RbTimeout);
                     }
                 }
             }
             1 => {
-                self.retries -= 1;
+                self.retries = self.retries.saturating_sub(1);
                 self.send.next = next;
                 return Err(Error::PrevBlkCrc);
             }
@@ -600,14 +1613,14 @@ impl<'a, E> T1Proto<'a, E> {
                 }
             }
             3 => {
-                self.retries -= 1;
+                self.retries = self.retries.saturating_sub(1);
                 self.state.request = true;
-                self.request = REQUEST_RESYNC;
+                self.request = Some(SBlockRequest::Resynch);
                 return Err(Error::RbResync);
             }
             _ => {
                 self.state.halt = true;
-                return Err(Error::RbNotSupported);
+                return Err(Error::RbNotSupported(pcb));
             }
         }
 
@@ -615,48 +1628,60 @@ impl<'a, E> T1Proto<'a, E> {
     }
 
     fn parse_request(&mut self) -> Result<(), Error<E>> {
-        let request = self.buf[1] & 0x3f;
+        let request = match (self.buf[1] & 0x20, SBlockRequest::from_pcb(self.buf[1])) {
+            (0, request) | (_, request @ SBlockRequest::VppError) => request,
+            // Unsolicited response is only acknowledged
+            _ => SBlockRequest::Other(self.buf[1] & 0x1f),
+        };
 
-        self.request = request;
+        self.request = Some(request);
         match request {
-            REQUEST_RESYNC => {
+            SBlockRequest::Resynch => {
                 return Err(Error::ReqResync);
             }
-            REQUEST_IFS => {
+            SBlockRequest::VppError => {
+                return Err(Error::VppError);
+            }
+            SBlockRequest::Ifs => {
                 if self.buf[2] != 1 {
                     return Err(Error::ErrorBadMsg1(self.buf[2]));
                 } else if self.buf[3] == 0 || self.buf[3] == 0xFF {
                     return Err(Error::ErrorBadMsg2(self.buf[2]));
+                } else if usize::from(self.buf[3]) + 3 + self.chk_algo_len() > self.buf_size {
+                    return Err(Error::IfsTooLarge(self.buf[3]));
                 } else {
                     self.ifs.card = self.buf[3];
                 }
             }
-            REQUEST_ABORT => {
+            SBlockRequest::Abort => {
                 if self.buf[2] == 0 {
                     self.state.aborted = true;
+                    self.state.chaining = false;
                     self.close_send_window();
                     self.close_recv_window();
                 } else {
                     return Err(Error::ErrorBadMsg3(self.buf[2]));
                 }
             }
-            REQUEST_WTX => {
+            SBlockRequest::Wtx => {
                 match self.buf[2] {
                     2.. => return Err(Error::ErrorBadMsg4(self.buf[2])),
                     1 => {
                         self.wtx.wtx = self.buf[3];
 
-                        // if (t1->wtx_max_value) - is always true
-                        if self.wtx.wtx > WTX_MAX_VALUE {
-                            self.wtx.wtx = WTX_MAX_VALUE;
+                        // Zero disables the limit
+                        if self.wtx_max_value != 0 && self.wtx.wtx > self.wtx_max_value {
+                            self.wtx.wtx = self.wtx_max_value;
                         }
 
-                        // if (t1->wtx_max_rounds) - is always true
-                        self.wtx.rounds -= 1;
-                        if self.wtx.rounds <= 0 {
-                            self.retries = 0;
-                            return Err(Error::NoRoundsLeft);
+                        if self.max_wtx_rounds != 0 {
+                            self.wtx.rounds -= 1;
+                            if self.wtx.rounds <= 0 {
+                                self.retries = 0;
+                                return Err(Error::NoRoundsLeft);
+                            }
                         }
+                        self.stats.wtx_granted += 1;
                     }
                     0 => (),
                 }
@@ -669,118 +1694,328 @@ impl<'a, E> T1Proto<'a, E> {
         Ok(())
     }
 
-    fn process<R, W>(&mut self, read: R, write: W) -> Result<(), Error<E>>
+    fn process<R, W>(
+        &mut self,
+        read: R,
+        write: W,
+        capdu: &[u8],
+        rapdu: &mut [u8],
+    ) -> Result<(), Error<E>>
     where
         R: Fn(&mut [u8]) -> Result<usize, E>,
         W: Fn(&[u8]) -> Result<usize, E>,
     {
-        let mut ret: Result<(), Error<E>> = Ok(());
+        self.process_begin();
+        while self.process_step(&read, &write, capdu, rapdu)? {}
+
+        self.process_end()
+    }
+
+    fn process_begin(&mut self) {
+        self.err = Ok(());
+        self.state.err_pending = false;
+        self.state.host_abort = false;
+        self.state.abort_pending = false;
+        if let Some(flag) = self.abort_flag {
+            flag.store(false, Ordering::Relaxed);
+        }
+        self.total_start = self.now();
+        self.total_slept = 0;
 
         if !self.soft_reset {
             self.need.reset = false;
         }
 
         self.process_init();
+    }
 
-        while !self.state.halt && self.retries > 0 {
-            self.request_init()?;
-            let n = write(&self.buf[..self.n]).map_err(Error::Write)?;
-            if n != self.n {
-                return Err(Error::WriteLen(self.n, n));
-            }
+    /// Take the host abort request, kept pending until S(ABORT request)
+    /// replaces the next block. Ignored while an S-block request waits for
+    /// its response.
+    fn take_abort(&mut self) -> bool {
+        if self.state.request {
+            return false;
+        }
+        let requested = self.abort_requested.swap(false, Ordering::Relaxed);
+        let raised = self
+            .abort_flag
+            .is_some_and(|f| f.swap(false, Ordering::Relaxed));
+        if requested || raised {
+            self.state.abort_pending = true;
+        }
 
-            if let Err(e) = self.read_block(&read) {
-                self.retries -= 1;
-                match e {
-                    Error::BadCrc(_, _) => self.state.badcrc = true,
-                    Error::Timeout(_) => self.state.timeout = true,
-                    _ => self.retries = 0,
-                }
-                ret = Err(e);
+        self.state.abort_pending
+    }
 
-                continue;
+    /// Write the whole block, continuing after partial writes. Gives up
+    /// after `MAX_RETRIES` writes in a row accepting no bytes.
+    fn write_block<W>(&self, write: &W) -> Result<(), Error<E>>
+    where
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        let total = self.n;
+        let mut sent = 0;
+        let mut retries = MAX_RETRIES;
+
+        while sent < total {
+            let n = write(&self.buf[sent..total]).map_err(Error::Write)?;
+            if n > total - sent {
+                return Err(Error::WriteLen(total - sent, n));
             }
-
-            if self.state.badcrc && self.buf[1] & 0xef == 0x81 {
-                self.retries -= 1;
-                ret = Err(Error::StateBadCrc);
-                continue;
+            if n == 0 {
+                retries -= 1;
+                if retries == 0 {
+                    return Err(Error::WritePartial { sent, total });
+                }
+            } else {
+                retries = MAX_RETRIES;
             }
+            sent += n;
+        }
+
+        Ok(())
+    }
+
+    /// Exchange one block, returns false when the exchange is over
+    pub(crate) fn process_step<R, W>(
+        &mut self,
+        read: &R,
+        write: &W,
+        capdu: &[u8],
+        rapdu: &mut [u8],
+    ) -> Result<bool, Error<E>>
+    where
+        R: Fn(&mut [u8]) -> Result<usize, E>,
+        W: Fn(&[u8]) -> Result<usize, E>,
+    {
+        if self.state.halt || self.retries == 0 {
+            return Ok(false);
+        }
 
+        (self.yield_cb)();
+        self.check_total_timeout()?;
+
+        // Abort requested by the host, replaces the next block
+        if self.take_abort() {
+            self.state.abort_pending = false;
+            self.close_send_window();
+            self.close_recv_window();
+            self.state.request = true;
             self.state.badcrc = false;
             self.state.timeout = false;
+            self.request = Some(SBlockRequest::Abort);
+            self.retries = MAX_RETRIES;
+            self.state.host_abort = true;
+            self.trace("abort request set");
+        }
 
-            if self.state.request {
-                if self.block_kind() == Block::S {
-                    match self.parse_response() {
-                        Ok(false) => (),
+        self.request_init(capdu)?;
+        self.write_block(write)?;
+        if let Some(cb) = self.trace_cb {
+            cb(Direction::Tx, &self.buf[..self.n]);
+        }
+        match block_kind(self.buf[1]) {
+            BlockKind::I => self.stats.tx_iblocks += 1,
+            BlockKind::R => self.stats.tx_rblocks += 1,
+            BlockKind::S => self.stats.tx_sblocks += 1,
+        }
 
-                        Ok(true) => {
-                            self.state.request = false;
-                            if self.recv_window_free_size() == 0 {
-                                self.state.halt = true;
-                            }
-                            self.retries = MAX_RETRIES;
-                            if self.request == REQUEST_RESET {
-                                self.state.request = true;
-                                self.request = REQUEST_IFS;
-                                self.ifs.dev = 254;
-                                self.need.ifsd_sync = true;
-                            }
-                            continue;
-                        }
+        // S(ABORT) response is sent, the aborted exchange is over
+        if self.state.aborted {
+            return Err(Error::Aborted);
+        }
 
-                        Err(e) => {
-                            ret = Err(e);
-                            self.state.halt = true;
-                            continue;
-                        }
-                    }
+        if let Err(e) = self.read_block(read) {
+            // Pending abort is sent at the next iteration
+            if matches!(e, Error::AbortRequested) {
+                return Ok(true);
+            }
+            self.retries = self.retries.saturating_sub(1);
+            self.trace("retry decrement");
+            match e {
+                Error::BadCrc(_, _) | Error::BadCrc16(_, _) => {
+                    self.stats.crc_errors += 1;
+                    self.state.badcrc = true;
+                    self.trace("badcrc");
                 }
+                Error::Timeout(_)
+                | Error::CwtTimeout { .. }
+                | Error::ReadHdrTimeout(_, _)
+                | Error::ReadDataTimeout(_, _) => {
+                    self.stats.timeouts += 1;
+                    self.state.timeout = true;
+                    self.trace("timeout");
+                }
+                _ => self.retries = 0,
+            }
+            self.set_err(Err(e));
 
-                self.retries -= 1;
-                ret = Err(Error::Ebade);
-            } else {
-                match self.block_kind() {
-                    Block::I => {
-                        self.retries = MAX_RETRIES;
-                        if self.send_window_size() != 0 {
-                            self.ack_iblock();
-                        }
+            return Ok(true);
+        }
+        match self.block_kind() {
+            BlockKind::I => self.stats.rx_iblocks += 1,
+            BlockKind::R => self.stats.rx_rblocks += 1,
+            BlockKind::S => self.stats.rx_sblocks += 1,
+        }
+
+        if self.state.badcrc && self.buf[1] & 0xef == 0x81 {
+            self.retries = self.retries.saturating_sub(1);
+            self.trace("retry decrement");
+            self.set_err(Err(Error::StateBadCrc));
+            return Ok(true);
+        }
+
+        self.state.badcrc = false;
+        self.state.timeout = false;
+
+        if !self.lenient {
+            if let Err(e) = self.check_pcb() {
+                self.set_err(Err(e));
+                self.state.halt = true;
+                return Ok(true);
+            }
+        }
 
-                        let n = self.parse_iblock();
-                        if self.state.aborted {
-                            continue;
+        if self.state.request {
+            if self.block_kind() == BlockKind::S {
+                self.trace("enter S-block parse");
+                match self.parse_response() {
+                    Ok(false) => (),
+
+                    Ok(true) => {
+                        if self.state.host_abort {
+                            return Err(Error::Aborted);
                         }
-                        if self.recv_size > self.recv_max {
-                            ret = Err(Error::RecvMsgSize(self.recv_size, self.recv_max));
+                        self.state.request = false;
+                        if self.recv_window_free_size() == 0 {
+                            self.truncated = self.recv.size > 0;
                             self.state.halt = true;
-                            continue;
                         }
-                        if n == 0 && self.send_window_size() == 0 {
-                            self.state.halt = true;
+                        self.retries = MAX_RETRIES;
+                        if self.request == Some(SBlockRequest::Reset) {
+                            self.state.request = true;
+                            self.request = Some(SBlockRequest::Ifs);
+                            self.ifs.dev = self.ifsd_max();
+                            self.need.ifsd_sync = true;
+                            self.trace("request set");
                         }
-                        self.wtx.rounds = MAX_WTX_ROUNDS;
+                        return Ok(true);
                     }
-                    Block::R => {
-                        ret = self.parse_rblock();
-                        self.wtx.rounds = MAX_WTX_ROUNDS;
+
+                    Err(e) => {
+                        self.set_err(Err(e));
+                        self.state.halt = true;
+                        return Ok(true);
                     }
-                    Block::S => {
-                        ret = self.parse_request();
-                        match ret {
-                            Ok(()) => self.state.reqresp = true,
-                            Err(Error::NoRoundsLeft) => (),
-                            Err(_) => self.state.halt = true,
-                        }
+                }
+            }
+
+            self.retries = self.retries.saturating_sub(1);
+            self.trace("retry decrement");
+            self.set_err(Err(Error::Ebade(self.buf[1])));
+        } else {
+            match self.block_kind() {
+                BlockKind::I => {
+                    self.trace("enter I-block parse");
+                    self.retries = MAX_RETRIES;
+                    self.ack_iblock();
+
+                    if self.buf[2] == 0 && self.buf[1] & 0x20 != 0 {
+                        self.set_err(Err(Error::EmptyChainBlock));
+                        self.state.halt = true;
+                        return Ok(true);
+                    }
+                    if self.buf[2] > self.ifs.dev {
+                        self.set_err(Err(Error::IblockTooLong(self.buf[2], self.ifs.dev)));
+                        self.state.halt = true;
+                        return Ok(true);
+                    }
+
+                    let n = self.parse_iblock(rapdu);
+                    self.state.chaining = n != 0;
+                    if self.state.aborted {
+                        return Ok(true);
+                    }
+                    if self.recv_size > self.recv_max {
+                        self.set_err(Err(Error::RecvMsgSize(self.recv_size, self.recv_max)));
+                        self.state.halt = true;
+                        return Ok(true);
+                    }
+                    if n == 0 && !self.send.open {
+                        self.state.halt = true;
+                    }
+                    self.wtx.rounds = self.max_wtx_rounds;
+                    self.set_err(Ok(()));
+                }
+                BlockKind::R => {
+                    self.trace("enter R-block parse");
+                    let ret = self.parse_rblock();
+                    self.set_err(ret);
+                    self.wtx.rounds = self.max_wtx_rounds;
+                }
+                BlockKind::S => {
+                    self.trace("enter S-block parse");
+                    let ret = self.parse_request();
+                    match ret {
+                        Ok(()) => self.state.reqresp = true,
+                        Err(Error::NoRoundsLeft) => (),
+                        Err(_) => self.state.halt = true,
                     }
+                    self.set_err(ret);
                 }
             }
         }
 
+        Ok(true)
+    }
+
+    /// Set the exchange result. A success keeps the last error, which is
+    /// then recovered.
+    fn set_err(&mut self, err: Result<(), Error<E>>) {
+        self.state.err_pending = err.is_err();
+        if err.is_err() {
+            self.err = err;
+        }
+    }
+
+    pub(crate) fn process_end(&mut self) -> Result<(), Error<E>> {
+        let mut ret = if self.state.err_pending {
+            core::mem::replace(&mut self.err, Ok(()))
+        } else {
+            Ok(())
+        };
+
+        if self.overflow {
+            return Err(Error::RecvBufferOverflow {
+                received: self.recv_size,
+                capacity: self.recv.size,
+            });
+        }
+
+        if self.state.halt {
+            self.trace("halt");
+            self.state.halted = ret.is_err();
+        } else {
+            self.trace("retries exhausted");
+            if let Some(cause) = ret.as_ref().err().and_then(RetryCause::of) {
+                ret = Err(Error::RetriesExhausted(cause));
+            }
+        }
+
         ret
     }
 
+    #[cfg(feature = "trace")]
+    fn trace(&self, msg: &str) {
+        if let Some(cb) = self.state_cb {
+            cb(msg);
+        }
+    }
+
+    #[cfg(not(feature = "trace"))]
+    #[inline(always)]
+    fn trace(&self, _msg: &str) {}
+
     fn zero(val: u8) -> u8 {
         if val == 0 {
             0
@@ -790,37 +2025,69 @@ impl<'a, E> T1Proto<'a, E> {
     }
 }
 
-impl<E> Default for T1Proto<'_, E> {
+impl<E> Default for T1Proto<E> {
     fn default() -> Self {
         Self {
             state: State::default(),
             ifs: Ifs::default(),
             nad: Nad::default(),
-            bwt: 300,
+            channel: 0,
+            channels: [None; CHANNELS_MAX],
+            bwt: BWT_DEFAULT,
+            nad_timeout: None,
+            total_timeout: None,
+            total_start: None,
+            total_slept: 0,
+            noise_max: None,
+            cwt: None,
             chk_algo: ChkAlgo::Lrc,
             retries: MAX_RETRIES,
-            request: 0xff,
+            request: None,
             wtx: Wtx::default(),
+            wtx_max_value: WTX_MAX_VALUE,
+            max_wtx_rounds: MAX_WTX_ROUNDS,
             need: Need::default(),
             atr: Atr::default(),
             send: Snd::default(),
             recv: Recv::default(),
             recv_max: RECV_MAX,
+            buf_size: BUF_SIZE,
             recv_size: 0,
+            overflow: false,
+            truncated: false,
             buf: [0; BUF_SIZE],
             n: 0,
-            sleep_cb: |_| (),
+            sleep_cb: None,
+            now_cb: None,
+            sim_time: None,
+            yield_cb: || (),
+            wait_progress_cb: None,
+            #[cfg(feature = "trace")]
+            state_cb: None,
+            trace_cb: None,
             soft_reset: false,
+            lenient: false,
+            any_card_nad: false,
+            block_read: false,
+            stats: Stats::default(),
+            abort_requested: AtomicBool::new(false),
+            abort_flag: None,
             err: Ok(()),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// ISO/IEC 7816 T=1 transmission protocol errors, re-exported as
+/// `T1Error`. Interface callback errors are kept in `ReadNad`, `ReadHdr`,
+/// `ReadData` and `Write`.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum Error<E> {
     CApduLen(usize),
     NoAtr,
-    NoRespIBlock,
+    NoRespIBlock(u8),
     ReadNad(E),
     ReadHdr(E),
     ReadData(E),
@@ -829,6 +2096,7 @@ pub enum Error<E> {
     ReadNadVal(u8),
     ReadLen255,
     BadCrc(u8, u8),
+    BadCrc16([u8; 2], [u8; 2]),
     Timeout(u32),
     WriteLen(usize, usize),
     ReadNadLen(usize, usize),
@@ -836,6 +2104,7 @@ pub enum Error<E> {
     ReadDataLen(usize, usize),
     RecvLen(usize, usize),
     Aborted,
+    AbortRequested,
     BadMsgIfs,
     BadMsgRst,
     NeverReq,
@@ -843,7 +2112,7 @@ pub enum Error<E> {
     PrevBlkCrc,
     RbHalt,
     RbResync,
-    RbNotSupported,
+    RbNotSupported(u8),
     ReqResync,
     ErrorBadMsg1(u8),
     ErrorBadMsg2(u8),
@@ -851,6 +2120,576 @@ pub enum Error<E> {
     ErrorBadMsg4(u8),
     NoRoundsLeft,
     StateBadCrc,
-    Ebade,
+    Ebade(u8),
     RecvMsgSize(usize, usize),
+    EmptyChainBlock,
+    PcbReserved(u8),
+    RbUnknown(u8),
+    ReqUnknown(u8),
+    NoCardResponse,
+    NoSyncByte(usize),
+    PpsRejected,
+    PpsMismatch,
+    CwtTimeout { received: usize, expected: usize },
+    RetriesExhausted(RetryCause),
+    IfsTooLarge(u8),
+    RecvBufferOverflow { received: usize, capacity: usize },
+    UnexpectedRblockSeq(u8),
+    InvalidIfsd(u8),
+    IblockTooLong(u8, u8),
+    ReadHdrTimeout(usize, usize),
+    ReadDataTimeout(usize, usize),
+    WritePartial { sent: usize, total: usize },
+    TotalTimeout(u32),
+    VppError,
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CApduLen(n) => write!(f, "command APDU length {n} is invalid"),
+            Self::NoAtr => f.write_str("no Answer To Reset"),
+            Self::NoRespIBlock(pcb) => write!(f, "no response I-block, last PCB 0x{pcb:02x}"),
+            Self::ReadNad(e) => write!(f, "reading NAD failed: {e}"),
+            Self::ReadHdr(e) => write!(f, "reading block header failed: {e}"),
+            Self::ReadData(e) => write!(f, "reading block data failed: {e}"),
+            Self::Write(e) => write!(f, "writing block failed: {e}"),
+            Self::ReadLen(n) => write!(f, "received block is too short: {n} bytes"),
+            Self::ReadNadVal(nad) => write!(f, "unexpected NAD 0x{nad:02x}"),
+            Self::ReadLen255 => f.write_str("reserved block length 255"),
+            Self::BadCrc(chk, recv) => {
+                write!(
+                    f,
+                    "bad checksum: computed 0x{chk:02x}, received 0x{recv:02x}"
+                )
+            }
+            Self::BadCrc16(chk, recv) => write!(
+                f,
+                "bad CRC: computed 0x{:02x}{:02x}, received 0x{:02x}{:02x}",
+                chk[0], chk[1], recv[0], recv[1]
+            ),
+            Self::Timeout(ms) => write!(f, "no block received within {ms} ms"),
+            Self::WriteLen(len, n) => write!(f, "short write: {n} of {len} bytes"),
+            Self::ReadNadLen(n, len) => write!(f, "short NAD read: {n} of {len} bytes"),
+            Self::ReadHdrLen(n, len) => write!(f, "short header read: {n} of {len} bytes"),
+            Self::ReadDataLen(n, len) => write!(f, "short data read: {n} of {len} bytes"),
+            Self::RecvLen(max, len) => {
+                write!(
+                    f,
+                    "block of {max} bytes with {len} data bytes exceeds the buffer"
+                )
+            }
+            Self::Aborted => f.write_str("transmission aborted"),
+            Self::AbortRequested => f.write_str("abort requested"),
+            Self::BadMsgIfs => f.write_str("bad S(IFS response)"),
+            Self::BadMsgRst => f.write_str("bad S(RESET response)"),
+            Self::NeverReq => f.write_str("S-block response to a request never sent"),
+            Self::RbTimeout => f.write_str("card did not acknowledge the block"),
+            Self::PrevBlkCrc => f.write_str("card received the block with a bad checksum"),
+            Self::RbHalt => f.write_str("R-block received after the exchange halted"),
+            Self::RbResync => f.write_str("card reported an error, resynchronizing"),
+            Self::RbNotSupported(pcb) => write!(f, "unsupported R-block PCB 0x{pcb:02x}"),
+            Self::ReqResync => f.write_str("card requested resynchronization"),
+            Self::ErrorBadMsg1(len) => write!(f, "bad S(IFS request) length {len}"),
+            Self::ErrorBadMsg2(len) => write!(f, "bad S(IFS request) value, length {len}"),
+            Self::ErrorBadMsg3(len) => write!(f, "bad S(ABORT request) length {len}"),
+            Self::ErrorBadMsg4(len) => write!(f, "bad S(WTX request) length {len}"),
+            Self::NoRoundsLeft => f.write_str("too many waiting time extensions"),
+            Self::StateBadCrc => f.write_str("checksum error on both sides"),
+            Self::Ebade(pcb) => write!(f, "unexpected block PCB 0x{pcb:02x}"),
+            Self::RecvMsgSize(n, max) => {
+                write!(f, "response of {n} bytes exceeds the maximum of {max}")
+            }
+            Self::EmptyChainBlock => f.write_str("empty chained I-block"),
+            Self::PcbReserved(pcb) => write!(f, "reserved PCB bits set: 0x{pcb:02x}"),
+            Self::RbUnknown(pcb) => write!(f, "unknown R-block PCB 0x{pcb:02x}"),
+            Self::ReqUnknown(pcb) => write!(f, "unknown S-block PCB 0x{pcb:02x}"),
+            Self::NoCardResponse => f.write_str("no response from the card"),
+            Self::NoSyncByte(n) => write!(f, "no NAD byte after {n} noise bytes"),
+            Self::PpsRejected => f.write_str("PPS request rejected"),
+            Self::PpsMismatch => f.write_str("PPS response mismatch"),
+            Self::CwtTimeout { received, expected } => write!(
+                f,
+                "character waiting time expired: {received} of {expected} bytes"
+            ),
+            Self::RetriesExhausted(cause) => write!(f, "retries exhausted: {cause}"),
+            Self::TotalTimeout(ms) => write!(f, "total transmission time exceeded: {ms} ms"),
+            Self::VppError => f.write_str("card reported VPP error"),
+            Self::IfsTooLarge(ifs) => write!(f, "card IFS {ifs} exceeds the block buffer"),
+            Self::RecvBufferOverflow { received, capacity } => write!(
+                f,
+                "response of {received} bytes overflows the {capacity} bytes buffer"
+            ),
+            Self::UnexpectedRblockSeq(pcb) => {
+                write!(f, "R-block acknowledges no sent block, PCB 0x{pcb:02x}")
+            }
+            Self::InvalidIfsd(ifsd) => write!(f, "invalid IFSD {ifsd}"),
+            Self::IblockTooLong(len, ifs) => {
+                write!(f, "I-block of {len} bytes exceeds the IFSD of {ifs}")
+            }
+            Self::ReadHdrTimeout(n, len) => {
+                write!(f, "block header timed out: {n} of {len} bytes")
+            }
+            Self::ReadDataTimeout(n, len) => {
+                write!(f, "block data timed out: {n} of {len} bytes")
+            }
+            Self::WritePartial { sent, total } => {
+                write!(f, "block write stalled: {sent} of {total} bytes")
+            }
+        }
+    }
+}
+
+impl<E> Error<E> {
+    /// Check if no block or character was received within the waiting
+    /// time, including exhausted retries after timeouts and the expired
+    /// total transmission time
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::TotalTimeout(_)) || self.cause() == Some(RetryCause::Timeout)
+    }
+
+    /// Check if a block error detection code mismatch was detected,
+    /// including exhausted retries after bad checksums
+    pub fn is_crc_error(&self) -> bool {
+        self.cause() == Some(RetryCause::BadCrc)
+    }
+
+    /// Convert the interface error type
+    pub fn map_io_err<F>(self, f: impl Fn(E) -> F) -> Error<F> {
+        match self {
+            Self::CApduLen(v) => Error::CApduLen(v),
+            Self::NoAtr => Error::NoAtr,
+            Self::NoRespIBlock(v) => Error::NoRespIBlock(v),
+            Self::ReadNad(v) => Error::ReadNad(f(v)),
+            Self::ReadHdr(v) => Error::ReadHdr(f(v)),
+            Self::ReadData(v) => Error::ReadData(f(v)),
+            Self::Write(v) => Error::Write(f(v)),
+            Self::ReadLen(v) => Error::ReadLen(v),
+            Self::ReadNadVal(v) => Error::ReadNadVal(v),
+            Self::ReadLen255 => Error::ReadLen255,
+            Self::BadCrc(a, b) => Error::BadCrc(a, b),
+            Self::BadCrc16(a, b) => Error::BadCrc16(a, b),
+            Self::Timeout(v) => Error::Timeout(v),
+            Self::WriteLen(a, b) => Error::WriteLen(a, b),
+            Self::ReadNadLen(a, b) => Error::ReadNadLen(a, b),
+            Self::ReadHdrLen(a, b) => Error::ReadHdrLen(a, b),
+            Self::ReadDataLen(a, b) => Error::ReadDataLen(a, b),
+            Self::RecvLen(a, b) => Error::RecvLen(a, b),
+            Self::Aborted => Error::Aborted,
+            Self::AbortRequested => Error::AbortRequested,
+            Self::BadMsgIfs => Error::BadMsgIfs,
+            Self::BadMsgRst => Error::BadMsgRst,
+            Self::NeverReq => Error::NeverReq,
+            Self::RbTimeout => Error::RbTimeout,
+            Self::PrevBlkCrc => Error::PrevBlkCrc,
+            Self::RbHalt => Error::RbHalt,
+            Self::RbResync => Error::RbResync,
+            Self::RbNotSupported(v) => Error::RbNotSupported(v),
+            Self::ReqResync => Error::ReqResync,
+            Self::ErrorBadMsg1(v) => Error::ErrorBadMsg1(v),
+            Self::ErrorBadMsg2(v) => Error::ErrorBadMsg2(v),
+            Self::ErrorBadMsg3(v) => Error::ErrorBadMsg3(v),
+            Self::ErrorBadMsg4(v) => Error::ErrorBadMsg4(v),
+            Self::NoRoundsLeft => Error::NoRoundsLeft,
+            Self::StateBadCrc => Error::StateBadCrc,
+            Self::Ebade(v) => Error::Ebade(v),
+            Self::RecvMsgSize(a, b) => Error::RecvMsgSize(a, b),
+            Self::EmptyChainBlock => Error::EmptyChainBlock,
+            Self::PcbReserved(v) => Error::PcbReserved(v),
+            Self::RbUnknown(v) => Error::RbUnknown(v),
+            Self::ReqUnknown(v) => Error::ReqUnknown(v),
+            Self::NoCardResponse => Error::NoCardResponse,
+            Self::NoSyncByte(v) => Error::NoSyncByte(v),
+            Self::PpsRejected => Error::PpsRejected,
+            Self::PpsMismatch => Error::PpsMismatch,
+            Self::CwtTimeout { received, expected } => Error::CwtTimeout { received, expected },
+            Self::RetriesExhausted(v) => Error::RetriesExhausted(v),
+            Self::IfsTooLarge(v) => Error::IfsTooLarge(v),
+            Self::RecvBufferOverflow { received, capacity } => {
+                Error::RecvBufferOverflow { received, capacity }
+            }
+            Self::UnexpectedRblockSeq(v) => Error::UnexpectedRblockSeq(v),
+            Self::InvalidIfsd(v) => Error::InvalidIfsd(v),
+            Self::IblockTooLong(a, b) => Error::IblockTooLong(a, b),
+            Self::ReadHdrTimeout(a, b) => Error::ReadHdrTimeout(a, b),
+            Self::ReadDataTimeout(a, b) => Error::ReadDataTimeout(a, b),
+            Self::WritePartial { sent, total } => Error::WritePartial { sent, total },
+            Self::TotalTimeout(v) => Error::TotalTimeout(v),
+            Self::VppError => Error::VppError,
+        }
+    }
+
+    fn cause(&self) -> Option<RetryCause> {
+        match self {
+            Self::RetriesExhausted(cause) => Some(*cause),
+            e => RetryCause::of(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ReadNad(e) | Self::ReadHdr(e) | Self::ReadData(e) | Self::Write(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicU32;
+
+    /// Card scripted by the bytes it sends, returned as they are read
+    struct Card<'a> {
+        resp: &'a [u8],
+        pos: Cell<usize>,
+    }
+
+    impl<'a> Card<'a> {
+        fn new(resp: &'a [u8]) -> Self {
+            Self {
+                resp,
+                pos: Cell::new(0),
+            }
+        }
+
+        /// Read callback returning as many of the next bytes as asked
+        fn read(&self, b: &mut [u8]) -> Result<usize, ()> {
+            let pos = self.pos.get();
+            let n = b.len().min(self.resp.len() - pos);
+            b[..n].copy_from_slice(&self.resp[pos..pos + n]);
+            self.pos.set(pos + n);
+            Ok(n)
+        }
+
+        /// Read callback returning one byte per call
+        fn read_byte(&self, b: &mut [u8]) -> Result<usize, ()> {
+            let n = b.len().min(1);
+            self.read(&mut b[..n])
+        }
+
+        /// Send the script again from its start
+        fn rewind(&self) {
+            self.pos.set(0);
+        }
+    }
+
+    fn byte_proto() -> T1Proto<()> {
+        let mut t1: T1Proto<()> = T1Proto::default();
+        t1.set_nad(0x15, 0x51);
+        t1.set_sleep_cb(Some(|_| ()));
+        t1
+    }
+
+    #[test]
+    fn test_block_timeout_saturates() {
+        let t1: T1Proto<()> = T1Proto {
+            bwt: u32::MAX - 1,
+            wtx: Wtx { wtx: 2, rounds: 1 },
+            ..Default::default()
+        };
+
+        assert_eq!(t1.block_timeout(), u32::MAX);
+    }
+
+    #[test]
+    fn test_rblock_retries_exhausted() {
+        let mut t1: T1Proto<()> = T1Proto {
+            retries: 0,
+            ..Default::default()
+        };
+
+        t1.buf[1] = 0x81;
+        assert_eq!(t1.parse_rblock(), Err(Error::PrevBlkCrc));
+        assert_eq!(t1.retries, 0);
+
+        t1.buf[1] = 0x83;
+        assert_eq!(t1.parse_rblock(), Err(Error::RbResync));
+        assert_eq!(t1.retries, 0);
+
+        t1.buf[1] = 0x80;
+        assert_eq!(t1.parse_rblock(), Err(Error::RbTimeout));
+        assert_eq!(t1.retries, 0);
+    }
+
+    #[test]
+    fn test_ack_iblock_once() {
+        let capdu = [0u8; 64];
+        let mut t1: T1Proto<()> = T1Proto::default();
+        t1.send.len = capdu.len();
+        t1.send.open = true;
+
+        t1.write_iblock(&capdu);
+        t1.ack_iblock();
+        t1.ack_iblock();
+
+        assert_eq!(t1.send.off, 32);
+        assert_eq!(t1.send.len, 32);
+        assert_eq!(t1.send.next, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "sleep_cb not set: call T1Proto::set_sleep_cb")]
+    fn test_sleep_cb_unset() {
+        let mut t1: T1Proto<()> = T1Proto::default();
+
+        let _ = t1.block_recv(|b: &mut [u8]| {
+            b[0] = 0;
+            Ok(1)
+        });
+    }
+
+    #[test]
+    fn test_now_cb_only() {
+        static NOW: AtomicU32 = AtomicU32::new(0);
+        let card = Card::new(&[0x15, 0x00, 0x00, 0x15]);
+        let mut t1: T1Proto<()> = T1Proto::default();
+        t1.set_nad(0x15, 0x51);
+        t1.set_now_cb(Some(|| NOW.fetch_add(1, Ordering::Relaxed)));
+
+        let ret = t1.block_recv(|b: &mut [u8]| card.read(b));
+
+        assert_eq!(ret, Ok(()));
+        assert!(NOW.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn test_block_kind() {
+        for pcb in 0..=u8::MAX {
+            let kind = match pcb {
+                0x00..=0x7f => BlockKind::I,
+                0x80..=0xbf => BlockKind::R,
+                0xc0..=0xff => BlockKind::S,
+            };
+            assert_eq!(block_kind(pcb), kind, "pcb {pcb:#04x}");
+        }
+    }
+
+    #[test]
+    fn test_is_chaining() {
+        for pcb in 0..=u8::MAX {
+            let chaining = pcb < 0x80 && pcb & 0x20 != 0;
+            assert_eq!(is_chaining(pcb), chaining, "pcb {pcb:#04x}");
+        }
+        assert!(is_chaining(0x20));
+        assert!(is_chaining(0x60));
+        assert!(!is_chaining(0x40));
+        assert!(!is_chaining(0xa0));
+        assert!(!is_chaining(0xe0));
+    }
+
+    #[test]
+    fn test_retries_remaining() {
+        let card = Card::new(&[
+            0x15, 0x00, 0x05, 0x9f, 0x7f, 0x55, 0x90, 0x00, 0x36, 0x15, 0x00, 0x05, 0x9f, 0x7f,
+            0x55, 0x90, 0x00, 0x35,
+        ]);
+        let capdu = [0x80, 0xca, 0x9f, 0x7f];
+        let read = |b: &mut [u8]| card.read(b);
+        let write = |b: &[u8]| Ok(b.len());
+
+        let mut rapdu = [0u8; 16];
+        let mut t1 = byte_proto();
+        let mut it = t1.transmit_iter(&capdu, &mut rapdu, read, write);
+        assert!(matches!(it.next(), Some(Ok(_))));
+        assert_eq!(it.retries_remaining(), MAX_RETRIES - 1);
+        assert!(it.by_ref().all(|ev| ev.is_ok()));
+        assert_eq!(t1.retries_remaining(), MAX_RETRIES);
+    }
+
+    #[test]
+    fn test_wtx_rounds_remaining() {
+        let wtx = [0x15, 0xc3, 0x01, 0x01, 0xd6];
+        let mut resp = wtx.repeat(5);
+        resp.extend_from_slice(&[0x15, 0x00, 0x05, 0x9f, 0x7f, 0x55, 0x90, 0x00, 0x35]);
+        let card = Card::new(&resp);
+        let capdu = [0x80, 0xca, 0x9f, 0x7f];
+        let read = |b: &mut [u8]| card.read(b);
+        let write = |b: &[u8]| Ok(b.len());
+
+        let mut rapdu = [0u8; 16];
+        let mut t1 = byte_proto();
+        let mut it = t1.transmit_iter(&capdu, &mut rapdu, read, write);
+        for _ in 0..5 {
+            assert!(matches!(it.next(), Some(Ok(_))));
+        }
+        assert_eq!(it.wtx_rounds_remaining(), MAX_WTX_ROUNDS - 5);
+        assert!(it.by_ref().all(|ev| ev.is_ok()));
+        assert_eq!(t1.wtx_rounds_remaining(), MAX_WTX_ROUNDS);
+    }
+
+    #[test]
+    fn test_transmit_iter() {
+        let card = Card::new(&[
+            0x15, 0x20, 0x02, 0x9f, 0x7f, 0xd7, 0x15, 0x40, 0x03, 0x55, 0x90, 0x00, 0x93,
+        ]);
+        let capdu = [0x80, 0xca, 0x9f, 0x7f];
+        let read = |b: &mut [u8]| card.read(b);
+        let write = |b: &[u8]| Ok(b.len());
+
+        let mut rapdu = [0u8; 16];
+        let mut t1 = byte_proto();
+        let mut it = t1.transmit_iter(&capdu, &mut rapdu, read, write);
+        assert_eq!(
+            it.next(),
+            Some(Ok(IterEvent {
+                send: [0x51, 0x00, 0x04],
+                recv: Some([0x15, 0x20, 0x02]),
+            }))
+        );
+        assert_eq!(
+            it.next(),
+            Some(Ok(IterEvent {
+                send: [0x51, 0x90, 0x00],
+                recv: Some([0x15, 0x40, 0x03]),
+            }))
+        );
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next(), None);
+        assert_eq!(it.received(), 5);
+
+        card.rewind();
+        let mut expected = [0u8; 16];
+        assert_eq!(
+            byte_proto().transmit(&capdu, &mut expected, read, write),
+            Ok(5)
+        );
+        assert_eq!(rapdu, expected);
+    }
+
+    #[test]
+    fn test_write_s_block() {
+        use SBlockDir::{Request, Response};
+
+        let table = [
+            (
+                SBlockRequest::Resynch,
+                Request,
+                [0x51, 0xc0, 0x00].as_slice(),
+            ),
+            (SBlockRequest::Resynch, Response, &[0x51, 0xe0, 0x00]),
+            (SBlockRequest::Ifs, Request, &[0x51, 0xc1, 0x01, 0xfe]),
+            (SBlockRequest::Ifs, Response, &[0x51, 0xe1, 0x01, 0x20]),
+            (SBlockRequest::Abort, Request, &[0x51, 0xc2, 0x00]),
+            (SBlockRequest::Abort, Response, &[0x51, 0xe2, 0x00]),
+            (SBlockRequest::Wtx, Request, &[0x51, 0xc3, 0x01, 0x02]),
+            (SBlockRequest::Wtx, Response, &[0x51, 0xe3, 0x01, 0x02]),
+            (SBlockRequest::Reset, Request, &[0x51, 0xc5, 0x00]),
+            (SBlockRequest::Reset, Response, &[0x51, 0xe5, 0x00]),
+        ];
+
+        let mut t1 = byte_proto();
+        t1.ifs.dev = 0xfe;
+        t1.ifs.card = 0x20;
+        t1.wtx.wtx = 2;
+        for (req, dir, block) in table {
+            t1.write_s_block(req, dir);
+            assert_eq!(&t1.buf[..t1.n - 1], block, "{req:?} {dir:?}");
+            assert_eq!(t1.buf[..t1.n].iter().fold(0, |c, b| c ^ b), 0);
+            assert_eq!(SBlockRequest::from_pcb(block[1]), req);
+        }
+    }
+
+    #[test]
+    fn test_block_recv_byte_by_byte() {
+        let resp = [0x15, 0x00, 0x05, 0x9f, 0x7f, 0x55, 0x90, 0x00, 0x35];
+        let card = Card::new(&resp);
+
+        let mut t1 = byte_proto();
+        assert_eq!(t1.block_recv(|b: &mut [u8]| card.read_byte(b)), Ok(()));
+        assert_eq!(t1.n, resp.len());
+        assert_eq!(&t1.buf[..3], &resp[..3]);
+        assert_eq!(t1.chk_is_good(), Ok(()));
+    }
+
+    #[test]
+    fn test_block_recv_header_timeout() {
+        let card = Card::new(&[0x15, 0x00]);
+
+        let mut t1 = byte_proto();
+        assert_eq!(
+            t1.block_recv(|b: &mut [u8]| card.read_byte(b)),
+            Err(Error::ReadHdrTimeout(1, 3))
+        );
+    }
+
+    #[test]
+    fn test_write_partial() {
+        let card = Card::new(&[0x15, 0x00, 0x02, 0x90, 0x00, 0x87]);
+        let calls = Cell::new(0);
+        let writes = RefCell::new(std::vec::Vec::new());
+
+        let read = |b: &mut [u8]| card.read(b);
+        let write = |b: &[u8]| {
+            let n = b.len().min(3);
+            writes.borrow_mut().extend_from_slice(&b[..n]);
+            calls.set(calls.get() + 1);
+            Ok(n)
+        };
+
+        let mut rapdu = [0u8; 8];
+        let mut t1 = byte_proto();
+        let ret = t1.transmit(&[0x00, 0x70, 0x00], &mut rapdu, read, write);
+
+        assert_eq!(ret, Ok(2));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(*writes.borrow(), [0x51, 0x00, 0x03, 0x00, 0x70, 0x00, 0x22]);
+    }
+
+    #[test]
+    fn test_write_stalled() {
+        let mut t1 = byte_proto();
+        t1.n = 7;
+
+        // Four bytes are accepted, then the transfer buffer stays full
+        let ret = t1.write_block(&|b: &[u8]| Ok(if b.len() == 7 { 4 } else { 0 }));
+        assert_eq!(ret, Err(Error::WritePartial { sent: 4, total: 7 }));
+    }
+
+    #[test]
+    fn test_abort_chain_closes_windows() {
+        let card = Card::new(&[0x15, 0xe2, 0x00, 0xf7]);
+        let pcb = Cell::new(0);
+
+        let mut t1 = byte_proto();
+        t1.state.chaining = true;
+        t1.send.len = 64;
+        t1.send.open = true;
+        t1.recv.len = 16;
+
+        let read = |b: &mut [u8]| card.read(b);
+        let write = |b: &[u8]| {
+            pcb.set(b[1]);
+            Ok(b.len())
+        };
+
+        assert_eq!(t1.abort(read, write), Ok(()));
+        assert_eq!(pcb.get(), 0xc2);
+        assert!(!t1.state.chaining);
+        assert!(!t1.send.open);
+        assert_eq!(t1.send.len, 0);
+        assert_eq!(t1.recv.len, 0);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn is_send<T: Send>() {}
+        fn is_sync<T: Sync>() {}
+
+        is_send::<T1Proto<()>>();
+        is_sync::<T1Proto<()>>();
+    }
+
+    #[test]
+    fn test_block_timeout_wtx() {
+        let mut t1: T1Proto<()> = T1Proto::default();
+        t1.wtx.wtx = 0;
+        assert_eq!(t1.block_timeout(), 300);
+
+        t1.wtx.wtx = 3;
+        assert_eq!(t1.block_timeout(), 900);
+    }
 }
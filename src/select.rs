@@ -0,0 +1,35 @@
+//! Application selection
+//!
+
+use crate::{Apdu, ApduError, Error, Response, Transmission};
+
+/// SELECT by DF name, first or only occurrence, FCI returned
+const SELECT_AID: [u8; 4] = [0x00, 0xa4, 0x04, 0x00];
+
+/// Application identifier length, ISO/IEC 7816-4
+const AID_LEN: core::ops::RangeInclusive<usize> = 5..=16;
+
+impl<T, E: Clone> Transmission<T, E> {
+    /// Select application by its identifier AID of 5 to 16 bytes and get
+    /// the response with the File Control Information. The remaining FCI
+    /// bytes are fetched with GET RESPONSE if automatic GET RESPONSE is
+    /// enabled.
+    pub fn select_aid<'r>(
+        &mut self,
+        aid: &[u8],
+        rapdu: &'r mut [u8],
+    ) -> Result<Response<'r>, Error<E>> {
+        if !AID_LEN.contains(&aid.len()) {
+            return Err(Error::Apdu(ApduError::AidLen(aid.len())));
+        }
+
+        let [cla, ins, p1, p2] = SELECT_AID;
+        let apdu = Apdu::new(cla, ins, p1, p2).data(aid).le(0);
+
+        if self.auto_get_response {
+            self.transmit_apdu_auto(&apdu, rapdu)
+        } else {
+            self.transmit_apdu(&apdu, rapdu)
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Response APDU status words SW1-SW2
+//!
+
+/// Status word SW1-SW2 with the commonly used values of ISO/IEC 7816-4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StatusWord {
+    /// 9000: Normal processing
+    Success,
+
+    /// 61XX: XX response bytes are still available, 00 meaning 256
+    MoreData(u8),
+
+    /// 6CXX: Wrong Le, XX is the exact length
+    WrongLe(u8),
+
+    /// 63CX: Verification failed, X retries left
+    RetriesLeft(u8),
+
+    /// 6700: Wrong length
+    WrongLength,
+
+    /// 6982: Security status not satisfied
+    SecurityNotSatisfied,
+
+    /// 6983: Authentication method blocked
+    AuthBlocked,
+
+    /// 6985: Conditions of use not satisfied
+    ConditionsNotSatisfied,
+
+    /// 6A80: Incorrect parameters in the command data field
+    IncorrectData,
+
+    /// 6A82: File or application not found
+    FileNotFound,
+
+    /// 6A86: Incorrect parameters P1-P2
+    IncorrectP1P2,
+
+    /// 6D00: Instruction code not supported or invalid
+    InsNotSupported,
+
+    /// 6E00: Class not supported
+    ClaNotSupported,
+
+    /// 6F00: No precise diagnosis
+    Unknown,
+
+    /// Any other status word
+    Other(u16),
+}
+
+impl StatusWord {
+    /// Interpret status words SW1 and SW2
+    pub fn new(sw1: u8, sw2: u8) -> Self {
+        match (sw1, sw2) {
+            (0x90, 0x00) => Self::Success,
+            (0x61, n) => Self::MoreData(n),
+            (0x6c, n) => Self::WrongLe(n),
+            (0x63, n) if n & 0xf0 == 0xc0 => Self::RetriesLeft(n & 0x0f),
+            (0x67, 0x00) => Self::WrongLength,
+            (0x69, 0x82) => Self::SecurityNotSatisfied,
+            (0x69, 0x83) => Self::AuthBlocked,
+            (0x69, 0x85) => Self::ConditionsNotSatisfied,
+            (0x6a, 0x80) => Self::IncorrectData,
+            (0x6a, 0x82) => Self::FileNotFound,
+            (0x6a, 0x86) => Self::IncorrectP1P2,
+            (0x6d, 0x00) => Self::InsNotSupported,
+            (0x6e, 0x00) => Self::ClaNotSupported,
+            (0x6f, 0x00) => Self::Unknown,
+            _ => Self::Other(u16::from_be_bytes([sw1, sw2])),
+        }
+    }
+
+    /// Status words as a 16-bit value
+    pub fn to_u16(self) -> u16 {
+        match self {
+            Self::Success => 0x9000,
+            Self::MoreData(n) => 0x6100 | u16::from(n),
+            Self::WrongLe(n) => 0x6c00 | u16::from(n),
+            Self::RetriesLeft(n) => 0x63c0 | u16::from(n),
+            Self::WrongLength => 0x6700,
+            Self::SecurityNotSatisfied => 0x6982,
+            Self::AuthBlocked => 0x6983,
+            Self::ConditionsNotSatisfied => 0x6985,
+            Self::IncorrectData => 0x6a80,
+            Self::FileNotFound => 0x6a82,
+            Self::IncorrectP1P2 => 0x6a86,
+            Self::InsNotSupported => 0x6d00,
+            Self::ClaNotSupported => 0x6e00,
+            Self::Unknown => 0x6f00,
+            Self::Other(sw) => sw,
+        }
+    }
+
+    /// Check for normal processing status, 9000 or 61XX
+    pub fn is_success(self) -> bool {
+        matches!(self, Self::Success | Self::MoreData(_))
+    }
+
+    /// Number of response bytes still available with 61XX status
+    pub fn remaining_bytes(self) -> Option<usize> {
+        match self {
+            Self::MoreData(0) => Some(256),
+            Self::MoreData(n) => Some(usize::from(n)),
+            _ => None,
+        }
+    }
+
+    /// Number of verification retries left with 63CX status
+    pub fn retries_left(self) -> Option<u8> {
+        match self {
+            Self::RetriesLeft(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl From<(u8, u8)> for StatusWord {
+    fn from((sw1, sw2): (u8, u8)) -> Self {
+        Self::new(sw1, sw2)
+    }
+}
+
+impl From<u16> for StatusWord {
+    fn from(sw: u16) -> Self {
+        let [sw1, sw2] = sw.to_be_bytes();
+        Self::new(sw1, sw2)
+    }
+}
+
+impl From<StatusWord> for u16 {
+    fn from(sw: StatusWord) -> Self {
+        sw.to_u16()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values() {
+        let table = [
+            (0x9000, StatusWord::Success),
+            (0x6110, StatusWord::MoreData(0x10)),
+            (0x6c20, StatusWord::WrongLe(0x20)),
+            (0x63c2, StatusWord::RetriesLeft(2)),
+            (0x6700, StatusWord::WrongLength),
+            (0x6982, StatusWord::SecurityNotSatisfied),
+            (0x6983, StatusWord::AuthBlocked),
+            (0x6985, StatusWord::ConditionsNotSatisfied),
+            (0x6a80, StatusWord::IncorrectData),
+            (0x6a82, StatusWord::FileNotFound),
+            (0x6a86, StatusWord::IncorrectP1P2),
+            (0x6d00, StatusWord::InsNotSupported),
+            (0x6e00, StatusWord::ClaNotSupported),
+            (0x6f00, StatusWord::Unknown),
+        ];
+
+        for (raw, sw) in table {
+            assert_eq!(StatusWord::from(raw), sw, "sw {raw:04x}");
+            assert_eq!(u16::from(sw), raw);
+        }
+    }
+
+    #[test]
+    fn test_other_values() {
+        for raw in [0x6300, 0x6281, 0x6a83, 0x9001, 0x6400] {
+            assert_eq!(StatusWord::from(raw), StatusWord::Other(raw));
+            assert_eq!(StatusWord::from(raw).to_u16(), raw);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for raw in 0..=u16::MAX {
+            assert_eq!(StatusWord::from(raw).to_u16(), raw);
+        }
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert!(StatusWord::from((0x90, 0x00)).is_success());
+        assert!(StatusWord::from((0x61, 0x00)).is_success());
+        assert!(!StatusWord::from((0x6a, 0x82)).is_success());
+
+        assert_eq!(StatusWord::from((0x61, 0x00)).remaining_bytes(), Some(256));
+        assert_eq!(StatusWord::from((0x61, 0x2d)).remaining_bytes(), Some(0x2d));
+        assert_eq!(StatusWord::Success.remaining_bytes(), None);
+
+        assert_eq!(StatusWord::from((0x63, 0xc0)).retries_left(), Some(0));
+        assert_eq!(StatusWord::from((0x63, 0xc3)).retries_left(), Some(3));
+        assert_eq!(StatusWord::from((0x63, 0x00)).retries_left(), None);
+    }
+}
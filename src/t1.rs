@@ -0,0 +1,45 @@
+//! Low-level ISO/IEC 7816 T=1 block engine
+//!
+//! `T1Proto` runs the block exchanges of `Transmission` without the
+//! connection interface management. The interface is accessed by the
+//! `read` and `write` closures passed to each exchange, the lifecycle of
+//! the interface is left to the caller.
+//!
+//! # Examples
+//! ```
+//! use core::cell::Cell;
+//! use iso7816_tx::t1::T1Proto;
+//!
+//! // Card response I-block with 9F7F55 9000
+//! let resp = [0x15, 0x00, 0x05, 0x9f, 0x7f, 0x55, 0x90, 0x00, 0x35];
+//! let pos = Cell::new(0);
+//!
+//! let read = |buf: &mut [u8]| -> Result<usize, ()> {
+//!     let n = buf.len().min(resp.len() - pos.get());
+//!     buf[..n].copy_from_slice(&resp[pos.get()..pos.get() + n]);
+//!     pos.set(pos.get() + n);
+//!     Ok(n)
+//! };
+//! let write = |buf: &[u8]| -> Result<usize, ()> { Ok(buf.len()) };
+//!
+//! let mut t1 = T1Proto::default();
+//! t1.set_nad(0x15, 0x51);
+//! t1.set_sleep_cb(Some(|_ms| ()));
+//!
+//! let mut rapdu = [0u8; 16];
+//! let n = t1.transmit(&[0x80, 0xca, 0x9f, 0x7f], &mut rapdu, read, write);
+//! assert_eq!(n, Ok(5));
+//! assert_eq!(rapdu[..5], [0x9f, 0x7f, 0x55, 0x90, 0x00]);
+//! assert_eq!(t1.sequence_numbers(), (1, 1));
+//! ```
+
+pub use crate::block::{
+    block_kind, is_chaining, BlockKind, ChkAlgo, SBlock, SBlockDir, SBlockRequest,
+};
+pub use crate::clock::SimulatedTime;
+pub use crate::proto::{
+    Direction, Error, IterEvent, PpsResult, ProtocolState, RetryCause, Stats, T1Iter, T1Proto,
+};
+
+#[cfg(feature = "alloc")]
+pub use crate::proto::dump_block;
@@ -1,5 +1,13 @@
 use hex_literal::hex;
-use iso7816_tx::{Error, Transmission, TransmissionBuilder};
+use iso7816_tx::atr::{
+    compute_tck, parse_atr, parse_atr_with_ts, validate_tck, AtrInfo, Convention,
+};
+use iso7816_tx::{
+    Apdu, ApduError, ChkAlgo, Direction, Error, PpsResult, ProtocolState, RetryCause, Set,
+    SimulatedTime, Stats, T1Error, Transmission, TransmissionBuilder,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 #[test]
 fn test_init() {
@@ -13,77 +21,2636 @@ fn test_reset() {
     assert_eq!(t.reset(), Ok(()));
 }
 
+static RST_EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn gpio_reset(active: bool) {
+    RST_EVENTS.lock().unwrap().push(format!("rst {active}"));
+}
+
+fn rst_sleep(ms: u32) {
+    RST_EVENTS.lock().unwrap().push(format!("sleep {ms}"));
+}
+
+#[test]
+fn test_reset_gpio() {
+    RST_EVENTS.lock().unwrap().clear();
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_reset_cb(|_| Err(()))
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(rst_sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_gpio_reset_cb(gpio_reset)
+        .set_reset_assert_delay_ms(5)
+        .set_reset_deassert_delay_ms(20)
+        .build();
+    assert_eq!(t.reset(), Ok(()));
+
+    assert_eq!(
+        *RST_EVENTS.lock().unwrap(),
+        ["rst true", "sleep 5", "rst false", "sleep 20"]
+    );
+}
+
+#[test]
+fn test_build_n() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let builder = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_reset_cb(reset)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV);
+
+    let readers: [Transmission<(), ()>; 4] = builder.build_n();
+    for mut t in readers {
+        set_resp(&hex!["1500059f7f55900035"]);
+        assert_eq!(t.transmit(capdu, &mut buf), Ok(&hex!["9f7f559000"][..]));
+    }
+
+    let mut t = builder.replicate().set_nad_timeout(20).build();
+    set_resp(&hex!["1500059f7f55900035"]);
+    assert_eq!(t.transmit(capdu, &mut buf), Ok(&hex!["9f7f559000"][..]));
+}
+
+#[test]
+fn test_validate() {
+    let builder = TransmissionBuilder::<(), ()>::new()
+        .set_init_cb(open)
+        .set_write_cb(write)
+        .set_card_nad(NAD_CARD);
+    let err = builder.validate().unwrap_err();
+    assert_eq!(err.missing, ["read_cb", "sleep_cb", "dev_nad"]);
+    assert_eq!(
+        format!("{err}"),
+        "missing builder options: read_cb sleep_cb dev_nad"
+    );
+
+    let builder = builder.set_read_block_cb(read).set_now_cb(|| 0);
+    assert_eq!(builder.validate().unwrap_err().missing, ["dev_nad"]);
+
+    let builder = builder.set_device_nad(NAD_DEV);
+    assert_eq!(builder.validate(), Ok(()));
+
+    let err = TransmissionBuilder::<(), ()>::new().validate().unwrap_err();
+    assert_eq!(
+        err.missing,
+        ["read_cb", "write_cb", "sleep_cb", "card_nad", "dev_nad"]
+    );
+}
+
+#[test]
+fn test_validate_channel() {
+    let builder = base_builder().set_channel_nad(1, NAD_CARD_CH1, NAD_DEV_CH1);
+    assert_eq!(builder.validate(), Ok(()));
+
+    for ch in [0, 4] {
+        let err = builder
+            .set_channel_nad(ch, 0x35, 0x53)
+            .validate()
+            .unwrap_err();
+        assert!(err.missing.is_empty());
+        assert_eq!(err.invalid_channel, Some(ch));
+        assert_eq!(format!("{err}"), format!("invalid logical channel {ch}"));
+    }
+
+    let err = TransmissionBuilder::<(), ()>::new()
+        .set_channel_nad(9, 0x35, 0x53)
+        .set_channel_nad(5, 0x35, 0x53)
+        .validate()
+        .unwrap_err();
+    assert_eq!(err.invalid_channel, Some(9));
+    assert_eq!(
+        format!("{err}"),
+        "missing builder options: read_cb write_cb sleep_cb card_nad dev_nad, \
+         invalid logical channel 9"
+    );
+}
+
+#[test]
+fn test_reset_cold_required() {
+    let builder = || {
+        TransmissionBuilder::new()
+            .set_init_cb(open)
+            .set_release_cb(close)
+            .set_read_cb(read)
+            .set_write_cb(write)
+            .set_sleep_cb(sleep)
+            .set_nad(NAD_CARD, NAD_DEV)
+            .require_cold_reset()
+    };
+
+    let mut t: Transmission<(), ()> = builder().build();
+    assert_eq!(t.reset(), Err(Error::NoResetCb));
+
+    let mut t = builder().set_reset_cb(reset).build();
+    assert_eq!(t.reset(), Ok(()));
+}
+
+#[test]
+fn test_transmit_apdu() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+
+    let mut t = transmission();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_wtx_max_rounds() {
+    let builder = || {
+        TransmissionBuilder::new()
+            .set_init_cb(open)
+            .set_release_cb(close)
+            .set_reset_cb(reset)
+            .set_read_cb(read)
+            .set_write_cb(write)
+            .set_sleep_cb(sleep)
+            .set_nad(NAD_CARD, NAD_DEV)
+    };
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    let mut t: Transmission<(), ()> = builder().set_wtx_max_rounds(3).build();
+    set_resps(&[
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+        &hex!["1500059f7f55900035"],
+    ]);
+    let ret = t.transmit(capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Ok([159, 127, 85, 144, 0])");
+    assert_eq!(
+        t.stats(),
+        Stats {
+            tx_iblocks: 1,
+            tx_sblocks: 2,
+            rx_iblocks: 1,
+            rx_sblocks: 2,
+            wtx_granted: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(t.wtx_rounds_remaining(), 3);
+
+    let mut t: Transmission<(), ()> = builder().set_wtx_max_rounds(2).build();
+    set_resps(&[&hex!["15c30101d6"], &hex!["15c30101d6"]]);
+    let ret = t.transmit(capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Err(T1(NoRoundsLeft))");
+    assert_eq!(t.wtx_rounds_remaining(), 0);
+}
+
+#[test]
+fn test_total_timeout() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_reset_cb(reset)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_simulated_time(&SIM_TIME)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_wtx_max_rounds(0)
+        .set_total_timeout(5)
+        .build();
+    let start = SIM_TIME.now();
+
+    // Waiting time extensions requested forever
+    set_resps(&[
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+        &hex!["15c30101d6"],
+    ]);
+    let ret = t.transmit(capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Err(T1(TotalTimeout(6)))");
+    assert!(ret.is_err_and(|e| e.is_timeout()));
+    assert_eq!(t.stats().wtx_granted, 2);
+    assert_eq!(SIM_TIME.now() - start, 6);
+}
+
+static GET_DATA: [u8; 4] = hex!["80ca9f7f"];
+
+#[test]
+fn test_transmit_static_capdu() {
+    let mut t = transmission();
+
+    // The command borrow is not tied to the transmission
+    let rapdu = {
+        let mut buf = [0u8; 258];
+        set_resp(&hex!["1500059f7f55900035"]);
+        t.transmit(&GET_DATA, &mut buf).map(<[u8]>::to_vec)
+    };
+    assert_eq!(rapdu, Ok(hex!["9f7f559000"].to_vec()));
+
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1540059f7f55900075"]);
+    assert_eq!(
+        t.transmit_static(&GET_DATA, &mut buf),
+        Ok(&hex!["9f7f559000"][..])
+    );
+}
+
+#[test]
+fn test_sequence_numbers() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = transmission();
+    assert_eq!(t.sequence_numbers(), (0, 0));
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    assert_eq!(t.transmit(capdu, &mut buf), Ok(&hex!["9f7f559000"][..]));
+    assert_eq!(t.sequence_numbers(), (1, 1));
+
+    set_resp(&hex!["1540059f7f55900075"]);
+    assert_eq!(t.transmit(capdu, &mut buf), Ok(&hex!["9f7f559000"][..]));
+    assert_eq!(t.sequence_numbers(), (0, 0));
+}
+
+#[test]
+fn test_transmit_traced() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+    let mut trace = String::new();
+
+    let mut t = transmission();
+    let n = t.transmit_traced(capdu, &mut buf, &mut trace);
+
+    assert_eq!(n, Ok(5));
+    assert_eq!(
+        trace,
+        "→ I[seq=0, len=4, M=0]: 80ca9f7f\n← I[seq=0, len=5, M=0]: 9f7f559000\n"
+    );
+}
+
+#[test]
+fn test_transmit_wrong_card_crc() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900000"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert!(matches!(ret, Err(Error::T1(_))));
+}
+
+#[test]
+fn test_transmit_last_error() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+    ]);
+
+    let mut t = transmission();
+    let err = t.transmit(capdu, &mut buf1).unwrap_err();
+    assert!(err.is_crc_error());
+    assert!(!err.is_timeout());
+    assert_eq!(
+        err.as_t1(),
+        Some(&T1Error::RetriesExhausted(RetryCause::BadCrc))
+    );
+    assert_eq!(
+        format!("{:?}", t.last_error()),
+        "Some(RetriesExhausted(BadCrc))"
+    );
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(t.last_error(), None);
+    assert_eq!(t.take_last_error(), None);
+
+    let mut t = base_builder().set_read_cb(|_, _| Err(())).build();
+    let err = t.transmit(capdu, &mut buf2).unwrap_err();
+    assert_eq!(err.as_t1(), t.last_error());
+    assert_eq!(format!("{:?}", t.take_last_error()), "Some(ReadNad(()))");
+    assert_eq!(t.last_error(), None);
+}
+
+#[test]
+fn test_transmit_wrong_card_nad() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["0000079f7f55900035"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert!(matches!(ret, Err(Error::T1(_))));
+}
+
+#[test]
+fn test_transmit_empty() {
+    let mut buf = [0u8; 258];
+    let capdu = &[];
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert!(matches!(ret, Err(Error::T1(_))));
+}
+
+#[test]
+fn test_transmit_too_long() {
+    let mut buf = [0u8; 258];
+    let capdu = &[0u8; 1024];
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert!(matches!(ret, Err(Error::T1(_))));
+}
+
+#[test]
+fn test_transmit_vpp_error() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    for resp in [&hex!["15e400f1"], &hex!["15c400d1"]] {
+        set_resp(resp);
+        let mut t = transmission();
+        let ret = t.transmit(capdu, &mut buf);
+
+        assert_eq!(format!("{ret:?}"), "Err(T1(VppError))");
+        assert_eq!(get_last_pcb(), 0x00);
+    }
+}
+
+#[test]
+fn test_protocol_state() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = transmission();
+    assert!(!t.is_inited());
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    t.transmit(capdu, &mut buf).expect("Transmit failed");
+    assert!(t.is_inited());
+    assert!(!t.needs_reset());
+    assert_eq!(
+        t.protocol_state(),
+        ProtocolState {
+            halted: false,
+            aborted: false,
+            send_seq: 1,
+            recv_seq: 1,
+        }
+    );
+
+    set_resp(&hex!["15c400d1"]);
+    let ret = t.transmit(capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Err(T1(VppError))");
+    assert!(t.protocol_state().halted);
+
+    t.release().expect("Release failed");
+    assert!(!t.is_inited());
+}
+
+#[test]
+fn test_transmit_aborted_by_card() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["15c200d7"]]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf1);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(Aborted))");
+    assert_eq!(get_last_pcb(), 0xe2);
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_busy_wait() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_now_cb(now)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_init_no_sleep() {
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build_unchecked();
+
+    assert_eq!(t.init(), Err(Error::NoSleepCb));
+}
+
+#[test]
+fn test_abort_chain() {
+    let mut buf = [0u8; 258];
+    let capdu = &[0u8; 64];
+    set_resp(&hex!["15900085"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+    assert!(matches!(ret, Err(Error::T1(_))));
+
+    set_resp(&hex!["15e200f7"]);
+    assert_eq!(t.abort(), Ok(()));
+    assert_eq!(get_last_pcb(), 0xc2);
+
+    set_last_pcb(0);
+    assert_eq!(t.abort(), Ok(()));
+    assert_eq!(get_last_pcb(), 0);
+}
+
+#[test]
+fn test_abort_transmit_request() {
+    let mut buf = [0u8; 258];
+    let capdu = &[0u8; 64];
+    set_resp(&hex!["15e200f7"]);
+    clear_writes();
+
+    let mut t = transmission();
+    t.abort_transmit();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(Aborted))");
+    assert_eq!(get_writes()[0], hex!["51c20093"]);
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(&hex!["80ca9f7f"], &mut buf);
+    assert_eq!(rapdu, Ok(&hex!["9f7f559000"][..]));
+}
+
+static ABORT: AtomicBool = AtomicBool::new(false);
+static ABORT_ON_READ: AtomicBool = AtomicBool::new(false);
+
+/// Raise the abort flag while the card response is read, once armed
+fn abort_read(interface: Option<&()>, buf: &mut [u8]) -> Result<usize, ()> {
+    if ABORT_ON_READ.swap(false, Ordering::Relaxed) {
+        ABORT.store(true, Ordering::Relaxed);
+    }
+
+    read(interface, buf)
+}
+
+#[test]
+fn test_abort_transmit() {
+    let mut buf = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &[0u8; 64];
+    set_resps(&[&hex!["15900085"], &hex!["15e200f7"]]);
+    clear_writes();
+
+    let mut t = base_builder()
+        .set_read_cb(abort_read)
+        .set_abort_flag(&ABORT)
+        .build();
+    ABORT.store(true, Ordering::Relaxed);
+    ABORT_ON_READ.store(true, Ordering::Relaxed);
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(Aborted))");
+    assert_eq!(get_writes()[0][1], 0x20);
+    assert_eq!(get_writes()[1], hex!["51c20093"]);
+    assert!(!ABORT.load(Ordering::Relaxed));
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(&hex!["80ca9f7f"], &mut buf2);
+    assert_eq!(rapdu, Ok(&hex!["9f7f559000"][..]));
+}
+
+#[cfg(feature = "trace")]
+static TRACE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "trace")]
+fn trace(msg: &str) {
+    TRACE.lock().unwrap().push(msg.to_string());
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_transmit_trace() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+    TRACE.lock().unwrap().clear();
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_state_cb(trace)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(*TRACE.lock().unwrap(), ["enter I-block parse", "halt"]);
+}
+
+#[test]
+fn test_transmit_yield() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+    set_yields(0);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_yield_cb(yield_now)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build();
+    t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert!(get_yields() >= 2);
+}
+
+static WAIT_PROGRESS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+#[test]
+fn test_transmit_wait_progress() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["000000" "1500059f7f55900035"]);
+    WAIT_PROGRESS.lock().unwrap().clear();
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_wait_progress_cb(|ms| WAIT_PROGRESS.lock().unwrap().push(ms))
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(*WAIT_PROGRESS.lock().unwrap(), [298, 296, 294, 292]);
+}
+
+#[test]
+fn test_needs_reset() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+
+    let mut t = transmission();
+    assert!(t.needs_reset());
+
+    t.transmit(capdu, &mut buf).expect("Transmit failed");
+    assert!(!t.needs_reset());
+
+    assert_eq!(t.release(), Ok(()));
+    assert!(t.needs_reset());
+}
+
+#[test]
+fn test_transmit_empty_resp() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["15000015"]);
+
+    let mut t = transmission();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &[]);
+}
+
+#[test]
+fn test_transmit_empty_chain_resp() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["15200035"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(EmptyChainBlock))");
+}
+
+#[test]
+fn test_transmit_empty_iblock() {
+    let mut buf = [0u8; 258];
+    let capdu = &[];
+    set_resp(&hex!["150002900087"]);
+
+    let mut t = transmission();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9000"]);
+    assert_eq!(get_last_pcb(), 0x00);
+}
+
+#[test]
+fn test_transmit_reserved_pcb() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1501059f7f55900034"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(PcbReserved(1)))");
+}
+
+#[test]
+fn test_transmit_reserved_pcb_lenient() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1501059f7f55900034"]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_lenient_pcb()
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_any_card_nad() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["2500059f7f55900005"]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .accept_any_card_nad()
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_other_card_nad_rejected() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["2500059f7f55900005"]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(ReadNadLen(0, 1)))");
+}
+
+#[test]
+fn test_transmit_unknown_rblock() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["158f009a"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(RbUnknown(143)))");
+}
+
+#[test]
+fn test_transmit_unexpected_rblock_seq() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["1520029f7fd7"], &hex!["15800095"]]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(UnexpectedRblockSeq(128)))");
+    assert_eq!(get_last_pcb(), 0x90);
+}
+
+#[test]
+fn test_transmit_unknown_request() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["15c700d2"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(ReqUnknown(199)))");
+}
+
+#[test]
+fn test_reconnect() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    let mut t = transmission();
+    set_resp(&hex!["1500059f7f55900035"]);
+    t.transmit(capdu, &mut buf1).expect("Transmit failed");
+
+    assert_eq!(t.release(), Ok(()));
+    assert_eq!(t.reconnect(), Ok(()));
+    assert_eq!(t.atr(), Ok(&[][..]));
+
+    set_resp(&hex!["1540059f7f55900075"]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_reconnect_failed() {
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .build_unchecked();
+
+    assert_eq!(t.reconnect(), Err(Error::NadNotSet));
+    assert_eq!(t.init(), Err(Error::NadNotSet));
+}
+
+#[test]
+fn test_individual_nad() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+    clear_writes();
+
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_device_nad(NAD_DEV)
+        .set_card_nad(NAD_CARD)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_writes()[0][0], NAD_DEV);
+}
+
+#[test]
+fn test_card_nad_only() {
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_card_nad(NAD_CARD)
+        .build_unchecked();
+    assert_eq!(t.init(), Err(Error::NadNotSet));
+
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_device_nad(NAD_DEV)
+        .build_unchecked();
+    assert_eq!(t.init(), Err(Error::NadNotSet));
+}
+
+#[test]
+fn test_transmit_nad_timeout() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&[0u8; 256]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_simulated_time(&SIM_TIME)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_nad_timeout(20)
+        .build();
+    let start = SIM_TIME.now();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(NoCardResponse))");
+    assert_eq!(SIM_TIME.now() - start, 22);
+}
+
+#[test]
+fn test_transmit_ifs() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e500f0"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resps(&[&hex!["15e101fe0b"], &hex!["1500059f7f55900035"]]);
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_ifs_wrong_value() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e500f0"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resp(&hex!["15e10120d5"]);
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(BadMsgIfs))");
+}
+
+#[test]
+fn test_transmit_ifs_wrong_len() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e500f0"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resp(&hex!["15e103fe000009"]);
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(BadMsgIfs))");
+}
+
+#[test]
+fn test_transmit_ifs_empty() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e500f0"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resp(&hex!["15e100f4"]);
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(BadMsgIfs))");
+}
+
+#[test]
+fn test_transmit_large_capdu() {
+    let mut buf = [0u8; 258];
+    let capdu: [u8; 512] = core::array::from_fn(|i| i as u8);
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15900085"],
+        &hex!["15800095"],
+        &hex!["150002900087"],
+    ]);
+    clear_writes();
+
+    let mut t = base_builder().enable_soft_reset().build();
+    let rapdu = t.transmit(&capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(rapdu, &hex!["9000"]);
+
+    let writes = get_writes();
+    let iblocks: Vec<&Vec<u8>> = writes.iter().filter(|b| b[1] & 0x80 == 0).collect();
+    assert_eq!(iblocks.len(), 3);
+
+    let mut data = Vec::new();
+    for (block, (pcb, len)) in iblocks.iter().zip([(0x20, 254), (0x60, 254), (0x00, 4)]) {
+        assert_eq!(block[0], NAD_DEV);
+        assert_eq!(block[1], pcb);
+        assert_eq!(usize::from(block[2]), len);
+        assert_eq!(block.len(), 3 + len + 1);
+        assert_eq!(block.iter().fold(0, |c, b| c ^ b), 0);
+        data.extend_from_slice(&block[3..3 + len]);
+    }
+    assert_eq!(data, capdu);
+}
+
+fn card_block(pcb: u8, data: &[u8]) -> &'static [u8] {
+    let mut block = vec![NAD_CARD, pcb, data.len() as u8];
+    block.extend_from_slice(data);
+    block.push(block.iter().fold(0, |c, b| c ^ b));
+
+    Vec::leak(block)
+}
+
+#[test]
+fn test_transmit_extended_apdu() {
+    let data: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+    let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data).le(0);
+    let mut capdu = vec![0u8; apdu.encoded_len()];
+    assert_eq!(apdu.write(&mut capdu), Ok(2009));
+    assert_eq!(&capdu[..7], &hex!["00d60000" "0007d0"]);
+    assert_eq!(&capdu[2007..], &hex!["0000"]);
+
+    let mut resp: Vec<u8> = data.iter().rev().copied().collect();
+    resp.extend_from_slice(&hex!["9000"]);
+
+    let mut resps = vec![
+        card_block(0xe5, &hex!["808131fe458b"]),
+        card_block(0xe1, &[0xfe]),
+    ];
+    let sent = capdu.chunks(254).count();
+    resps.extend((1..sent).map(|i| card_block(0x80 | ((i as u8 & 1) << 4), &[])));
+    let chunks: Vec<&[u8]> = resp.chunks(254).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 0x20 } else { 0x00 };
+        resps.push(card_block(((i as u8 & 1) << 6) | more, chunk));
+    }
+    set_resps(Vec::leak(resps));
+    clear_writes();
+
+    let mut buf = vec![0u8; 2002];
+    let mut t = base_builder().enable_soft_reset().build();
+    let rapdu = t.transmit(&capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(rapdu, &resp[..]);
+
+    let writes = get_writes();
+    let sent: Vec<u8> = writes
+        .iter()
+        .filter(|b| b[1] & 0x80 == 0)
+        .flat_map(|b| b[3..b.len() - 1].to_vec())
+        .collect();
+    assert_eq!(sent, capdu);
+}
+
+#[test]
+fn test_atr_rejected() {
+    set_resp(&hex!["15e50100f1"]);
+
+    let mut t = base_builder()
+        .set_atr_filter(|atr| atr.starts_with(&[0x80, 0x81, 0x31]))
+        .enable_soft_reset()
+        .build();
+    assert_eq!(t.atr(), Err(Error::AtrRejected));
+}
+
+#[test]
+fn test_atr_accepted() {
+    set_resp(&hex!["15e506808131fe458bf6"]);
+
+    let mut t = base_builder()
+        .set_atr_filter(|atr| atr.starts_with(&[0x80, 0x81, 0x31]))
+        .enable_soft_reset()
+        .build();
+    assert_eq!(t.atr(), Ok(&hex!["808131fe458b"][..]));
+}
+
+static RETRY_SLEEPS: Mutex<usize> = Mutex::new(0);
+
+fn retry_sleep(ms: u32) {
+    if ms == 10 {
+        *RETRY_SLEEPS.lock().unwrap() += 1;
+    }
+}
+
+#[test]
+fn test_atr_with_retries() {
+    set_resps(&[
+        &hex!["15e506808131fe458b00"],
+        &hex!["15e506808131fe458b00"],
+        &hex!["15e506808131fe458b00"],
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+    ]);
+    *RETRY_SLEEPS.lock().unwrap() = 0;
+
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_reset_cb(reset)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(retry_sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_soft_reset()
+        .build();
+
+    assert_eq!(t.atr_with_retries(2), Ok(&hex!["808131fe458b"][..]));
+    assert_eq!(*RETRY_SLEEPS.lock().unwrap(), 1);
+
+    set_resps(&[
+        &hex!["15e506808131fe458b00"],
+        &hex!["15e506808131fe458b00"],
+        &hex!["15e506808131fe458b00"],
+    ]);
+    let ret = t.atr_with_retries(1);
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(BadCrc)))");
+}
+
+#[test]
+fn test_invalidate_atr() {
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e506808131fe458bf6"]);
+    assert_eq!(t.atr(), Ok(&hex!["808131fe458b"][..]));
+
+    set_resp(&hex!["15e5043b02145089"]);
+    assert_eq!(t.atr(), Ok(&hex!["808131fe458b"][..]));
+
+    t.invalidate_atr();
+    assert!(t.needs_reset());
+    assert_eq!(t.atr(), Ok(&hex!["3b021450"][..]));
+    assert!(!t.needs_reset());
+}
+
+#[test]
+fn test_retries_remaining() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = transmission();
+
+    set_resps(&[&hex!["1500059f7f55900036"], &hex!["1500059f7f55900035"]]);
+    t.transmit(capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(t.t1_retries_remaining(), 3);
+
+    set_resps(&[
+        &hex!["1540059f7f55900074"],
+        &hex!["1540059f7f55900074"],
+        &hex!["1540059f7f55900074"],
+    ]);
+    let ret = t.transmit(capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(BadCrc)))");
+    assert_eq!(t.t1_retries_remaining(), 0);
+}
+
+#[test]
+fn test_recovered_error() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = transmission();
+
+    set_resps(&[&hex!["1500059f7f55900036"], &hex!["1500059f7f55900035"]]);
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(format!("{:?}", t.recovered_error()), "Some(BadCrc(53, 54))");
+    assert_eq!(t.last_error(), None);
+
+    set_resp(&hex!["1540059f7f55900075"]);
+    t.transmit(capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(format!("{:?}", t.recovered_error()), "None");
+
+    set_resps(&[
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+    ]);
+    assert!(t.transmit(capdu, &mut buf).is_err());
+    assert_eq!(format!("{:?}", t.recovered_error()), "None");
+    assert!(t.last_error().is_some_and(|e| e.is_crc_error()));
+}
+
+#[test]
+fn test_transmit_after_resync() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    let mut t = transmission();
+    set_resp(&hex!["1500059f7f55900035"]);
+    t.transmit(capdu, &mut buf1).expect("Transmit failed");
+
+    set_resps(&[
+        &hex!["15830096"],
+        &hex!["15e000f5"],
+        &hex!["1500059f7f55900035"],
+    ]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_last_pcb(), 0x00);
+}
+
+#[test]
+fn test_resync() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    let mut t = transmission();
+    set_resp(&hex!["1500059f7f55900035"]);
+    t.transmit(capdu, &mut buf1).expect("Transmit failed");
+
+    set_resp(&hex!["15e000f5"]);
+    assert_eq!(t.resync(), Ok(()));
+    assert_eq!(get_last_pcb(), 0xc0);
+    assert_eq!(
+        t.stats(),
+        Stats {
+            tx_iblocks: 1,
+            tx_sblocks: 1,
+            rx_iblocks: 1,
+            rx_sblocks: 1,
+            resyncs: 1,
+            ..Default::default()
+        }
+    );
+    t.reset_stats();
+    assert_eq!(t.stats(), Stats::default());
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_last_pcb(), 0x00);
+}
+
+#[test]
+fn test_resync_bad_response() {
+    let mut t = transmission();
+    set_resps(&[
+        &hex!["15e101fe0b"],
+        &hex!["15e101fe0b"],
+        &hex!["15e101fe0b"],
+    ]);
+
+    let ret = t.resync();
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(BadBlock)))");
+}
+
+#[test]
+fn test_transmit_after_soft_reset() {
+    let mut buf1 = [0u8; 258];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["1500059f7f55900035"],
+    ]);
+    t.transmit(capdu, &mut buf1).expect("Transmit failed");
+
+    set_resp(&hex!["15e506808131fe458bf6"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resps(&[&hex!["15e101fe0b"], &hex!["1500059f7f55900035"]]);
+    let rapdu = t.transmit(capdu, &mut buf2).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_last_pcb(), 0x00);
+}
+
+#[test]
+fn test_ifs_after_soft_reset() {
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resps(&[&hex!["15e506808131404535f6"], &hex!["15e101fe0b"]]);
+    assert_eq!(t.reset(), Ok(()));
+
+    assert_eq!(t.card_ifs(), 64);
+    assert_eq!(t.dev_ifs(), 0xfe);
+}
+
+#[test]
+fn test_parse_atr_t0() {
+    let info = parse_atr(&hex!["021450"]);
+
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0,
+            protocols: 1,
+            ifsc: None,
+            bwi: None,
+            cwi: None,
+            tck_valid: true,
+        }
+    );
+    assert!(!info.t1());
+}
+
+#[test]
+fn test_parse_atr_t1() {
+    let info = parse_atr(&hex!["808131fe458b"]);
+
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0,
+            protocols: 2,
+            ifsc: Some(0xfe),
+            bwi: Some(4),
+            cwi: Some(5),
+            tck_valid: true,
+        }
+    );
+    assert!(info.t1());
+}
+
+#[test]
+fn test_parse_atr_t0_t1() {
+    let info = parse_atr(&hex!["808011feef"]);
+
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0,
+            protocols: 3,
+            ifsc: Some(0xfe),
+            bwi: None,
+            cwi: None,
+            tck_valid: true,
+        }
+    );
+}
+
+#[test]
+fn test_parse_atr_one_historical_byte() {
+    let info = parse_atr(&hex!["31954580"]);
+
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0,
+            protocols: 1,
+            ifsc: None,
+            bwi: None,
+            cwi: None,
+            tck_valid: true,
+        }
+    );
+
+    let info = parse_atr(&hex!["91958131fe45808f"]);
+
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0,
+            protocols: 2,
+            ifsc: Some(0xfe),
+            bwi: Some(4),
+            cwi: Some(5),
+            tck_valid: true,
+        }
+    );
+}
+
+#[test]
+fn test_parse_atr_direct_convention() {
+    let info = parse_atr_with_ts(&hex!["3b808131fe458b"]);
+
+    assert_eq!(info.ts_byte(), 0x3b);
+    assert_eq!(info.convention(), Convention::Direct);
+    assert!(info.is_direct_convention());
+    assert!(!info.is_inverse_convention());
+    assert_eq!(
+        info,
+        AtrInfo {
+            ts: 0x3b,
+            ..parse_atr(&hex!["808131fe458b"])
+        }
+    );
+}
+
+#[test]
+fn test_parse_atr_inverse_convention() {
+    let info = parse_atr_with_ts(&hex!["3f021450"]);
+
+    assert_eq!(info.ts_byte(), 0x3f);
+    assert_eq!(info.convention(), Convention::Inverse);
+    assert!(!info.is_direct_convention());
+    assert!(info.is_inverse_convention());
+    assert!(info.tck_valid);
+}
+
+#[test]
+fn test_parse_atr_unknown_convention() {
+    assert_eq!(parse_atr(&hex!["021450"]).convention(), Convention::Unknown);
+    assert_eq!(
+        parse_atr_with_ts(&hex!["3e021450"]).convention(),
+        Convention::Unknown
+    );
+    assert_eq!(parse_atr_with_ts(&[]).convention(), Convention::Unknown);
+}
+
+#[test]
+fn test_parse_atr_bad_tck() {
+    let info = parse_atr(&hex!["808131fe4500"]);
+
+    assert!(info.t1());
+    assert!(!info.tck_valid);
+}
+
+#[test]
+fn test_atr_bwt() {
+    assert_eq!(parse_atr(&hex!["808131fe458b"]).bwt(), Some(1602));
+    assert_eq!(parse_atr(&hex!["808131fe058b"]).bwt(), Some(102));
+    assert_eq!(parse_atr(&hex!["808131fea58b"]).bwt(), None);
+    assert_eq!(parse_atr(&hex!["021450"]).bwt(), None);
+}
+
+#[test]
+fn test_apply_atr_timings() {
+    set_resp(&hex!["15e506808131fe458bf6"]);
+
+    let mut t = base_builder().enable_soft_reset().build();
+    assert_eq!(t.bwt(), 300);
+
+    t.reset().expect("Reset failed");
+    t.apply_atr_timings();
+    assert_eq!(t.bwt(), 1602);
+}
+
+#[test]
+fn test_parse_atr_empty() {
+    assert!(!parse_atr(&[]).tck_valid);
+}
+
+#[test]
+fn test_release_once() {
+    set_releases(0);
+
+    let mut t = transmission();
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(t.release(), Ok(()));
+    assert_eq!(t.release(), Ok(()));
+    drop(t);
+
+    assert_eq!(get_releases(), 1);
+}
+
+static SESSION_EVENTS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+#[test]
+fn test_session_hooks() {
+    SESSION_EVENTS.lock().unwrap().clear();
+
+    let mut t = base_builder()
+        .set_on_session_start(|| SESSION_EVENTS.lock().unwrap().push("start"))
+        .set_on_session_end(|| SESSION_EVENTS.lock().unwrap().push("end"))
+        .build();
+    assert!(SESSION_EVENTS.lock().unwrap().is_empty());
+
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(*SESSION_EVENTS.lock().unwrap(), ["start"]);
+
+    assert_eq!(t.release(), Ok(()));
+    assert_eq!(t.release(), Ok(()));
+    drop(t);
+    assert_eq!(*SESSION_EVENTS.lock().unwrap(), ["start", "end"]);
+}
+
+#[test]
+fn test_session_hooks_reconnect() {
+    SESSION_EVENTS.lock().unwrap().clear();
+
+    let mut t = base_builder()
+        .set_on_session_start(|| SESSION_EVENTS.lock().unwrap().push("start"))
+        .set_on_session_end(|| SESSION_EVENTS.lock().unwrap().push("end"))
+        .build();
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(t.reconnect(), Ok(()));
+    drop(t);
+
+    assert_eq!(
+        *SESSION_EVENTS.lock().unwrap(),
+        ["start", "end", "start", "end"]
+    );
+}
+
+#[test]
+fn test_into_parts() {
+    set_releases(0);
+
+    let mut t: Transmission<u32, ()> = TransmissionBuilder::new()
+        .set_init_cb(|| Ok(Some(7)))
+        .set_release_cb(|_| {
+            set_releases(get_releases() + 1);
+            Ok(None)
+        })
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build_unchecked();
+    assert_eq!(t.init(), Ok(()));
+
+    let parts = t.into_parts();
+    assert_eq!(parts.interface, Some(7));
+    assert!(parts.release_cb.is_some());
+    assert!(parts.read_cb.is_none());
+    assert_eq!(get_releases(), 0);
+}
+
+static INITS: Mutex<usize> = Mutex::new(0);
+
+#[test]
+fn test_keep_interface() {
+    set_releases(0);
+    *INITS.lock().unwrap() = 0;
+
+    let mut t: Transmission<u32, ()> = TransmissionBuilder::new()
+        .set_init_cb(|| {
+            *INITS.lock().unwrap() += 1;
+            Ok(Some(7))
+        })
+        .set_release_cb(|_| {
+            set_releases(get_releases() + 1);
+            Ok(None)
+        })
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .keep_interface()
+        .build_unchecked();
+
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(t.release(), Ok(()));
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(t.release(), Ok(()));
+    assert_eq!(t.init(), Ok(()));
+    assert_eq!(*INITS.lock().unwrap(), 1);
+    assert_eq!(get_releases(), 0);
+
+    drop(t);
+    assert_eq!(get_releases(), 1);
+}
+
+#[test]
+fn test_transmit_rblock_retries() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["15810094"], &hex!["15810094"], &hex!["15810094"]]);
+    clear_writes();
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(CardNak)))");
+    assert_eq!(get_writes().len(), 3);
+    assert_eq!(
+        t.stats(),
+        Stats {
+            tx_iblocks: 3,
+            rx_rblocks: 3,
+            retransmissions: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_transmit_bad_crc_retries() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+    ]);
+    clear_writes();
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(BadCrc)))");
+    assert_eq!(get_writes().len(), 3);
+    assert_eq!(
+        t.stats(),
+        Stats {
+            tx_iblocks: 1,
+            tx_rblocks: 2,
+            crc_errors: 3,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_auto_get_response() {
+    let mut work = [0u8; 64];
+    set_resps(&[&hex!["15000601020304610274"], &hex!["15400405069000c2"]]);
+    clear_writes();
+
+    let mut t = transmission();
+    let mut iter = t.auto_get_response(&hex!["80ca9f7f00"], &hex!["6104"], &mut work);
+    let first = iter.next().unwrap().expect("GET RESPONSE failed");
+    let second = iter.next().unwrap().expect("GET RESPONSE failed");
+
+    assert!(iter.next().is_none());
+    assert_eq!(first, &hex!["010203046102"]);
+    assert_eq!(second, &hex!["05069000"]);
+
+    let writes = get_writes();
+    assert_eq!(&writes[0][3..8], &hex!["00c0000004"]);
+    assert_eq!(&writes[1][3..8], &hex!["00c0000002"]);
+}
+
+#[test]
+fn test_auto_get_response_none() {
+    let mut work = [0u8; 64];
+    clear_writes();
+
+    let mut t = transmission();
+    let mut iter = t.auto_get_response(&hex!["80ca9f7f00"], &hex!["9000"], &mut work);
+
+    assert!(iter.next().is_none());
+    assert!(get_writes().is_empty());
+}
+
+#[test]
+fn test_auto_get_response_channel() {
+    let mut work = [0u8; 64];
+    set_resps(&[&hex!["15000601020304610274"], &hex!["15400405069000c2"]]);
+    clear_writes();
+
+    let mut t = transmission();
+    let iter = t.auto_get_response(&hex!["83ca9f7f00"], &hex!["6104"], &mut work);
+    assert!(iter
+        .collect::<Result<Vec<_>, _>>()
+        .is_ok_and(|r| r.len() == 2));
+
+    set_resps(&[&hex!["15000601020304610274"], &hex!["15400405069000c2"]]);
+    let mut iter = t.auto_get_response(&hex!["45ca9f7f00"], &hex!["6104"], &mut work);
+    iter.next().unwrap().expect("GET RESPONSE failed");
+
+    let writes = get_writes();
+    assert_eq!(&writes[0][3..8], &hex!["03c0000004"]);
+    assert_eq!(&writes[1][3..8], &hex!["03c0000002"]);
+    assert_eq!(&writes[2][3..8], &hex!["45c0000004"]);
+}
+
+#[test]
+fn test_transmit_into() {
+    let mut t = transmission();
+
+    for resp in [hex!["1500059f7f55900035"], hex!["1540059f7f55900075"]] {
+        let mut buf = [0u8; 258];
+        let capdu = hex!["80ca9f7f"];
+        set_resp(Box::leak(Box::new(resp)));
+
+        let n = t.transmit_into(&capdu, &mut buf).expect("Transmit failed");
+        assert_eq!(&buf[..n], &hex!["9f7f559000"]);
+    }
+}
+
+struct Driver {
+    t: Transmission<(), ()>,
+}
+
+impl Driver {
+    fn get_data(&mut self) -> Result<[u8; 5], Error<()>> {
+        let mut buf = [0u8; 258];
+        let rapdu = self.t.transmit(&hex!["80ca9f7f"], &mut buf)?;
+
+        Ok(rapdu.try_into().unwrap())
+    }
+}
+
+#[test]
+fn test_transmit_embedded() {
+    set_resp(&hex!["1500059f7f55900035"]);
+
+    let mut driver = Driver { t: transmission() };
+
+    assert_eq!(driver.get_data(), Ok(hex!["9f7f559000"]));
+}
+
+#[test]
+fn test_transmit_apdu_builder() {
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1500059f7f55900035"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x80, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t.transmit_apdu(&apdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(resp.data(), &hex!["9f7f55"]);
+    assert_eq!(resp.sw(), 0x9000);
+    assert_eq!(&get_writes()[0][3..8], &hex!["80ca9f7f00"]);
+}
+
+#[test]
+fn test_transmit_apdu_short_resp() {
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1500019084"]);
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x80, 0xca, 0x9f, 0x7f).le(0);
+
+    assert_eq!(
+        t.transmit_apdu(&apdu, &mut buf),
+        Err(Error::Apdu(ApduError::RespTooShort(1)))
+    );
+}
+
+#[test]
+fn test_transmit_apdu_too_long() {
+    let mut buf = [0u8; 258];
+    let data = [0u8; 300];
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data);
+
+    assert_eq!(
+        t.transmit_apdu(&apdu, &mut buf),
+        Err(Error::Apdu(ApduError::BufTooSmall(264, 307)))
+    );
+}
+
+#[test]
+fn test_response_blocks() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["1520029f7fd7"], &hex!["15400355900093"]]);
+    clear_writes();
+
+    let mut t = transmission();
+    let mut blocks = t.response_blocks(capdu, &mut buf);
+
+    assert_eq!(blocks.next(), Some(Ok(&hex!["9f7f"][..])));
+    assert_eq!(get_writes().len(), 1);
+    assert_eq!(blocks.next(), Some(Ok(&hex!["559000"][..])));
+    assert_eq!(blocks.next(), None);
+    assert_eq!(get_writes()[1], hex!["519000c1"]);
+}
+
+static BLOCK_TRACE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn block_trace(dir: Direction, block: &[u8]) {
+    let hex: String = block.iter().map(|b| format!("{b:02x}")).collect();
+    BLOCK_TRACE.lock().unwrap().push(format!("{dir:?} {hex}"));
+}
+
+#[test]
+fn test_trace_cb() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15810094"],
+        &hex!["1500059f7f55900035"],
+    ]);
+    BLOCK_TRACE.lock().unwrap().clear();
+
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_soft_reset()
+        .set_trace_cb(block_trace)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(
+        *BLOCK_TRACE.lock().unwrap(),
+        [
+            "Tx 51c50094",
+            "Rx 15e506808131fe458bf6",
+            "Tx 51c101fe6f",
+            "Rx 15e101fe0b",
+            "Tx 51000480ca9f7fff",
+            "Rx 15810094",
+            "Tx 51000480ca9f7fff",
+            "Rx 1500059f7f55900035",
+        ]
+    );
+}
+
+#[test]
+fn test_transmit_on_channels() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[
+        &hex!["1500059f7f55900035"],
+        &hex!["2500029000b7"],
+        &hex!["1540029000c7"],
+        &hex!["2540029000f7"],
+    ]);
+    clear_writes();
+
+    let mut t: Transmission<(), ()> = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_reset_cb(reset)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .set_channel_nad(1, NAD_CARD_CH1, NAD_DEV_CH1)
+        .build();
+
+    assert_eq!(
+        t.transmit_on(0, capdu, &mut buf),
+        Ok(&hex!["9f7f559000"][..])
+    );
+    assert_eq!(t.transmit_on(1, capdu, &mut buf), Ok(&hex!["9000"][..]));
+    assert_eq!(t.transmit_on(0, capdu, &mut buf), Ok(&hex!["9000"][..]));
+    assert_eq!(t.transmit_on(1, capdu, &mut buf), Ok(&hex!["9000"][..]));
+
+    let prologues: Vec<_> = get_writes().iter().map(|w| w[..2].to_vec()).collect();
+    assert_eq!(
+        prologues,
+        [[0x51, 0x00], [0x52, 0x00], [0x51, 0x40], [0x52, 0x40]]
+    );
+
+    let ret = t.transmit_on(2, capdu, &mut buf);
+    assert_eq!(format!("{ret:?}"), "Err(UnknownChannel(2))");
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_dump_block() {
+    use iso7816_tx::dump_block;
+
+    assert_eq!(
+        dump_block(&hex!["51000480ca9f7fff"]),
+        "NAD=51 PCB=00(I,seq0) LEN=04 DATA=80ca9f7f EDC=ff"
+    );
+    assert_eq!(
+        dump_block(&hex!["1560029f7fd7"]),
+        "NAD=15 PCB=60(I,seq1,M) LEN=02 DATA=9f7f EDC=d7"
+    );
+    assert_eq!(
+        dump_block(&hex!["15810094"]),
+        "NAD=15 PCB=81(R,seq0,err1) LEN=00 EDC=94"
+    );
+    assert_eq!(
+        dump_block(&hex!["51c101fe6f"]),
+        "NAD=51 PCB=c1(S,IFS req) LEN=01 DATA=fe EDC=6f"
+    );
+    assert_eq!(dump_block(&hex!["5100"]), "?5100");
+}
+
+static BLOCK_READS: Mutex<usize> = Mutex::new(0);
+
+fn read_block(interface: Option<&()>, buf: &mut [u8]) -> Result<usize, ()> {
+    *BLOCK_READS.lock().unwrap() += 1;
+    read(interface, buf)
+}
+
+#[test]
+fn test_transmit_read_block_cb() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900035"]);
+    *BLOCK_READS.lock().unwrap() = 0;
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(|_, _| Err(()))
+        .set_read_block_cb(read_block)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(*BLOCK_READS.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_ifs_too_large() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["15c101fe2b"]);
+
+    let mut t = base_builder().set_block_buf_size(64).build();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(IfsTooLarge(254)))");
+}
+
+#[test]
+fn test_ifs_fits() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["15c1013ce9"], &hex!["1500059f7f55900035"]]);
+    clear_writes();
+
+    let mut t = base_builder().set_block_buf_size(64).build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_writes()[1], hex!["51e1013c8d"]);
+}
+
+#[test]
+fn test_ifsd_buf_size() {
+    let mut t = base_builder().set_block_buf_size(64).build();
+    set_resp(&hex!["15e1013cc9"]);
+    clear_writes();
+
+    assert_eq!(t.sync_ifs(), Ok(60));
+    assert_eq!(get_writes()[0], hex!["51c1013cad"]);
+}
+
+#[test]
+fn test_recv_buffer_overflow() {
+    let mut buf = [0u8; 5];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500069f7f5566900050"]);
+
+    let mut t = transmission();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(
+        format!("{ret:?}"),
+        "Err(T1(RecvBufferOverflow { received: 6, capacity: 5 }))"
+    );
+}
+
+#[test]
+fn test_response_truncated() {
+    let mut buf1 = [0u8; 5];
+    let mut buf2 = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500069f7f5566900050"]);
+
+    let mut t = transmission();
+    assert!(!t.response_truncated());
+    assert!(t.transmit(capdu, &mut buf1).is_err());
+    assert!(t.response_truncated());
+
+    set_resp(&hex!["1540069f7f5566900010"]);
+    t.transmit(capdu, &mut buf2).expect("Transmit failed");
+    assert!(!t.response_truncated());
+}
+
+#[test]
+fn test_transmit_apdu_auto() {
+    let mut buf = [0u8; 64];
+    set_resps(&[
+        &hex!["1500040102610370"],
+        &hex!["154005030405610231"],
+        &hex!["1500040607900080"],
+    ]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x81, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t
+        .transmit_apdu_auto(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.data(), &hex!["01020304050607"]);
+    assert_eq!(resp.sw(), 0x9000);
+
+    let writes = get_writes();
+    assert_eq!(&writes[1][3..8], &hex!["01c0000003"]);
+    assert_eq!(&writes[2][3..8], &hex!["01c0000002"]);
+}
+
+#[test]
+fn test_transmit_apdu_auto_buf_full() {
+    let mut buf = [0u8; 8];
+    set_resp(&hex!["1500040102611063"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x00, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t
+        .transmit_apdu_auto(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.data(), &hex!["0102"]);
+    assert_eq!(resp.sw(), 0x6110);
+    assert_eq!(get_writes().len(), 1);
+}
+
+#[test]
+fn test_transmit_apdu_auto_le_256() {
+    let mut buf = [0u8; 257];
+    set_resp(&hex!["150002610076"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x00, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t
+        .transmit_apdu_auto(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x6100);
+    assert_eq!(get_writes().len(), 1);
+}
+
+#[test]
+fn test_transmit_apdu_wrong_le_256() {
+    let mut buf = [0u8; 258];
+    set_resps(&[&hex!["1500026c007b"], &hex!["1540049f7f900021"]]);
+    clear_writes();
+
+    let mut t = base_builder().enable_wrong_le_retry().build();
+    let apdu = Apdu::new(0x00, 0xca, 0x9f, 0x7f).le(0x10);
+    let resp = t.transmit_apdu(&apdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(resp.data(), &hex!["9f7f"]);
+    assert_eq!(resp.sw(), 0x9000);
+
+    let writes = get_writes();
+    assert_eq!(writes.len(), 2);
+    assert_eq!(&writes[0][3..8], &hex!["00ca9f7f10"]);
+    assert_eq!(&writes[1][3..8], &hex!["00ca9f7f00"]);
+}
+
+#[test]
+fn test_transmit_apdu_wrong_le_once() {
+    let mut buf = [0u8; 258];
+    set_resps(&[&hex!["1500026c106b"], &hex!["1540026c201b"]]);
+    clear_writes();
+
+    let mut t = base_builder().enable_wrong_le_retry().build();
+    let apdu = Apdu::new(0x00, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t.transmit_apdu(&apdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x6c20);
+
+    let writes = get_writes();
+    assert_eq!(writes.len(), 2);
+    assert_eq!(&writes[1][3..8], &hex!["00ca9f7f10"]);
+}
+
+#[test]
+fn test_transmit_apdu_wrong_le_disabled() {
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1500026c106b"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x00, 0xca, 0x9f, 0x7f).le(0);
+    let resp = t.transmit_apdu(&apdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x6c10);
+    assert_eq!(get_writes().len(), 1);
+}
+
+fn chained_commands() -> Vec<Vec<u8>> {
+    let mut cmds = Vec::new();
+    let mut cmd = Vec::new();
+
+    for block in get_writes().iter().filter(|b| b[1] & 0x80 == 0) {
+        cmd.extend_from_slice(&block[3..block.len() - 1]);
+        if block[1] & 0x20 == 0 {
+            cmds.push(core::mem::take(&mut cmd));
+        }
+    }
+
+    cmds
+}
+
+#[test]
+fn test_transmit_chained_one() {
+    let mut buf = [0u8; 258];
+    let data = [0x5a; 10];
+    set_resp(&hex!["150002900087"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let apdu = Apdu::new(0x01, 0xd6, 0x00, 0x00).data(&data).le(0);
+    let resp = t
+        .transmit_chained(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x9000);
+
+    let cmds = chained_commands();
+    assert_eq!(cmds.len(), 1);
+    assert_eq!(&cmds[0][..5], &hex!["01d600000a"]);
+    assert_eq!(cmds[0].len(), 4 + 1 + 10 + 1);
+}
+
+#[test]
+fn test_transmit_chained_two() {
+    let mut buf = [0u8; 258];
+    let data: [u8; 300] = core::array::from_fn(|i| i as u8);
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15900085"],
+        &hex!["150002900087"],
+        &hex!["1540029f7fb7"],
+    ]);
+    clear_writes();
+
+    let mut t = base_builder().enable_soft_reset().build();
+    let apdu = Apdu::new(0x01, 0xd6, 0x00, 0x00).data(&data).le(0);
+    let resp = t
+        .transmit_chained(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x9f7f);
+
+    let cmds = chained_commands();
+    assert_eq!(cmds.len(), 2);
+    assert_eq!(&cmds[0][..5], &hex!["11d60000ff"]);
+    assert_eq!(&cmds[0][5..], &data[..255]);
+    assert_eq!(&cmds[1][..5], &hex!["01d600002d"]);
+    assert_eq!(&cmds[1][5..50], &data[255..]);
+    assert_eq!(&cmds[1][50..], &[0x00]);
+}
+
+#[test]
+fn test_transmit_chained_three() {
+    let mut buf = [0u8; 258];
+    let data: [u8; 600] = core::array::from_fn(|i| i as u8);
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15900085"],
+        &hex!["150002900087"],
+        &hex!["15900085"],
+        &hex!["1540029000c7"],
+        &hex!["150002900087"],
+    ]);
+    clear_writes();
+
+    let mut t = base_builder().enable_soft_reset().build();
+    let apdu = Apdu::new(0x84, 0xd6, 0x00, 0x00).data(&data);
+    let resp = t
+        .transmit_chained(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x9000);
+
+    let cmds = chained_commands();
+    assert_eq!(cmds.len(), 3);
+    assert_eq!(&cmds[0][..5], &hex!["94d60000ff"]);
+    assert_eq!(&cmds[1][..5], &hex!["94d60000ff"]);
+    assert_eq!(&cmds[2][..5], &hex!["84d600005a"]);
+    assert_eq!(
+        cmds.iter()
+            .map(|c| c[5..].to_vec())
+            .collect::<Vec<_>>()
+            .concat(),
+        data
+    );
+}
+
 #[test]
-fn test_transmit_apdu() {
+fn test_transmit_chained_rejected() {
+    let mut buf = [0u8; 258];
+    let data = [0x5a; 300];
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15900085"],
+        &hex!["150002630074"],
+    ]);
+
+    let mut t = base_builder().enable_soft_reset().build();
+    let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data);
+    let ret = t.transmit_chained(&apdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(ChainRejected(25344))");
+}
+
+#[test]
+fn test_transmit_chained_warning() {
+    let mut buf = [0u8; 258];
+    let data = [0x5a; 300];
+    set_resps(&[
+        &hex!["15e506808131fe458bf6"],
+        &hex!["15e101fe0b"],
+        &hex!["15900085"],
+        &hex!["150002630074"],
+        &hex!["1540029000c7"],
+    ]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_soft_reset()
+        .enable_chain_warnings()
+        .build();
+    let apdu = Apdu::new(0x00, 0xd6, 0x00, 0x00).data(&data);
+    let resp = t
+        .transmit_chained(&apdu, &mut buf)
+        .expect("Transmit failed");
+
+    assert_eq!(resp.sw(), 0x9000);
+}
+
+const AID: [u8; 7] = hex!["a0000000031010"];
+
+#[test]
+fn test_select_aid() {
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1500076f038401aa9000c1"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let resp = t.select_aid(&AID, &mut buf).expect("Select failed");
+
+    assert_eq!(resp.data(), &hex!["6f038401aa"]);
+    assert_eq!(resp.sw(), 0x9000);
+    assert_eq!(&get_writes()[0][3..16], &hex!["00a4040007a000000003101000"]);
+}
+
+#[test]
+fn test_select_aid_not_found() {
+    let mut buf = [0u8; 258];
+    set_resp(&hex!["1500026a82ff"]);
+
+    let mut t = transmission();
+    let resp = t.select_aid(&AID, &mut buf).expect("Select failed");
+
+    assert!(resp.data().is_empty());
+    assert_eq!(resp.sw(), 0x6a82);
+}
+
+#[test]
+fn test_select_aid_get_response() {
+    let mut buf = [0u8; 258];
+    set_resps(&[&hex!["1500046f0361031f"], &hex!["1540058401aa9000ef"]]);
+    clear_writes();
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_auto_get_response()
+        .build();
+    let resp = t.select_aid(&AID, &mut buf).expect("Select failed");
+
+    assert_eq!(resp.data(), &hex!["6f038401aa"]);
+    assert_eq!(resp.sw(), 0x9000);
+    assert_eq!(&get_writes()[1][3..8], &hex!["00c0000003"]);
+}
+
+#[test]
+fn test_select_aid_len() {
     let mut buf = [0u8; 258];
+
+    let mut t = transmission();
+    assert_eq!(
+        t.select_aid(&AID[..4], &mut buf),
+        Err(Error::Apdu(ApduError::AidLen(4)))
+    );
+    assert_eq!(
+        t.select_aid(&[0; 17], &mut buf),
+        Err(Error::Apdu(ApduError::AidLen(17)))
+    );
+}
+
+#[test]
+fn test_transmit_to_hex_writer() {
     let capdu = &hex!["80ca9f7f"];
-    set_resp(&hex!["1500059f7f55900035"]);
+    set_resps(&[&hex!["1520029f7fd7"], &hex!["15400355900093"]]);
+    let mut out = String::new();
 
     let mut t = transmission();
-    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+    let n = t.transmit_to_hex_writer(capdu, &mut out);
 
-    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(n, Ok(5));
+    assert_eq!(out, "9f7f559000");
 }
 
 #[test]
-fn test_transmit_wrong_card_crc() {
+fn test_block_transmit_last_error() {
     let mut buf = [0u8; 258];
     let capdu = &hex!["80ca9f7f"];
-    set_resp(&hex!["1500059f7f55900000"]);
+    set_resps(&[
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+        &hex!["1500059f7f55900000"],
+    ]);
+
+    let mut t = transmission();
+    let mut blocks = t.response_blocks(capdu, &mut buf);
+    assert!(blocks.next().is_some_and(|b| b.is_err()));
+    assert_eq!(
+        format!("{:?}", t.last_error()),
+        "Some(RetriesExhausted(BadCrc))"
+    );
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let mut out = String::new();
+    assert_eq!(t.transmit_to_hex_writer(capdu, &mut out), Ok(5));
+    assert_eq!(t.last_error(), None);
+}
+
+#[test]
+fn test_compute_tck() {
+    assert_eq!(compute_tck(&hex!["3b808131fe458b"]), 0x8b);
+    assert_eq!(compute_tck(&hex!["3b808131fe4500"]), 0x8b);
+    assert_eq!(compute_tck(&hex!["3b021450"]), 0x46);
+    assert_eq!(compute_tck(&[]), 0);
+}
+
+#[test]
+fn test_validate_tck() {
+    assert!(validate_tck(&hex!["3b808131fe458b"]));
+    assert!(validate_tck(&hex!["3b808011feef"]));
+    assert!(!validate_tck(&hex!["3b808131fe4500"]));
+    assert!(validate_tck(&hex!["3b021450"]));
+    assert!(!validate_tck(&hex!["3b"]));
+}
+
+#[test]
+fn test_transmit_chain_rblock_acks() {
+    let mut buf = [0u8; 258];
+    let capdu: [u8; 64] = core::array::from_fn(|i| i as u8);
+    set_resps(&[&hex!["15900085"], &hex!["15800095"], &hex!["150002900087"]]);
+    clear_writes();
 
     let mut t = transmission();
+    let rapdu = t.transmit(&capdu, &mut buf).expect("Transmit failed");
+    assert_eq!(rapdu, &hex!["9000"]);
+
+    let writes = get_writes();
+    let pcbs: Vec<u8> = writes.iter().map(|b| b[1]).collect();
+    assert_eq!(pcbs, [0x20, 0x40, 0x80]);
+
+    let data: Vec<u8> = writes[..2].iter().flat_map(|b| b[3..35].to_vec()).collect();
+    assert_eq!(data, capdu);
+}
+
+#[test]
+fn test_reset_unexpected_block_pcb() {
+    set_resps(&[&hex!["15000015"], &hex!["15000015"], &hex!["15000015"]]);
+
+    let mut t = base_builder().enable_soft_reset().build();
+    assert_eq!(
+        format!("{:?}", t.reset()),
+        "Err(T1(RetriesExhausted(BadBlock)))"
+    );
+}
+
+#[test]
+fn test_transmit_unsupported_rblock_pcb() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["15840091"]);
+
+    let mut t = TransmissionBuilder::new()
+        .set_init_cb(open)
+        .set_release_cb(close)
+        .set_read_cb(read)
+        .set_write_cb(write)
+        .set_sleep_cb(sleep)
+        .set_nad(NAD_CARD, NAD_DEV)
+        .enable_lenient_pcb()
+        .build();
     let ret = t.transmit(capdu, &mut buf);
 
-    assert!(matches!(ret, Err(Error::T1(_))));
+    assert_eq!(format!("{ret:?}"), "Err(T1(RbNotSupported(132)))");
 }
 
 #[test]
-fn test_transmit_wrong_card_nad() {
+fn test_transmit_recv_max_exceeded() {
+    let mut buf = [0u8; 512];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().set_recv_max(256).build();
+    set_resp(&hex!["15e101fe0b"]);
+    assert_eq!(t.negotiate_ifsd(254), Ok(254));
+
+    set_resps(long_resp());
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(RecvMsgSize(300, 256)))");
+}
+
+#[test]
+fn test_transmit_iblock_too_long() {
     let mut buf = [0u8; 258];
     let capdu = &hex!["80ca9f7f"];
-    set_resp(&hex!["0000079f7f55900035"]);
+    set_resp(iblock(0x00, &[0x33; 33]));
 
     let mut t = transmission();
     let ret = t.transmit(capdu, &mut buf);
 
-    assert!(matches!(ret, Err(Error::T1(_))));
+    assert_eq!(format!("{ret:?}"), "Err(T1(IblockTooLong(33, 32)))");
 }
 
 #[test]
-fn test_transmit_empty() {
+fn test_transmit_recv_max() {
+    let mut buf = [0u8; 512];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().set_recv_max(300).build();
+    set_resp(&hex!["15e101fe0b"]);
+    assert_eq!(t.negotiate_ifsd(254), Ok(254));
+
+    set_resps(long_resp());
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu.len(), 300);
+}
+
+#[test]
+fn test_init_recv_max_too_small() {
+    let mut t = base_builder().set_recv_max(1).build();
+    assert_eq!(t.init(), Err(Error::RecvMaxTooSmall(1)));
+}
+
+#[test]
+fn test_negotiate_ifsd() {
+    let mut t = transmission();
+    set_resp(&hex!["15e1018075"]);
+    clear_writes();
+
+    assert_eq!(t.negotiate_ifsd(0x80), Ok(0x80));
+    assert_eq!(&get_writes()[0][1..4], &hex!["c10180"]);
+}
+
+#[test]
+fn test_negotiate_ifsd_lowered() {
+    let mut t = transmission();
+    set_resp(&hex!["15e10140b5"]);
+
+    assert_eq!(t.negotiate_ifsd(0x80), Ok(0x40));
+}
+
+#[test]
+fn test_negotiate_ifsd_raised() {
+    let mut t = transmission();
+    set_resp(&hex!["15e101a055"]);
+
+    let ret = t.negotiate_ifsd(0x80);
+    assert_eq!(format!("{ret:?}"), "Err(T1(BadMsgIfs))");
+}
+
+#[test]
+fn test_negotiate_ifsd_invalid() {
+    let mut t = transmission();
+
+    let ret = t.negotiate_ifsd(0);
+    assert_eq!(format!("{ret:?}"), "Err(T1(InvalidIfsd(0)))");
+
+    let ret = t.negotiate_ifsd(255);
+    assert_eq!(format!("{ret:?}"), "Err(T1(InvalidIfsd(255)))");
+}
+
+#[test]
+fn test_sync_ifs() {
     let mut buf = [0u8; 258];
-    let capdu = &[];
+    let capdu = &hex!["80ca9f7f"];
+    let mut t = base_builder().enable_soft_reset().build();
+    set_resp(&hex!["15e506808131fe458bf6"]);
+    assert_eq!(t.reset(), Ok(()));
+
+    set_resp(&hex!["15e101fe0b"]);
+    assert_eq!(t.sync_ifs(), Ok(0xfe));
+    assert_eq!(get_last_pcb(), 0xc1);
+
+    set_resp(&hex!["1500059f7f55900035"]);
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_noise() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["ffffffffffffffff 3c9e00a7 1500059f7f55900035"]);
+
+    let mut t = base_builder().set_noise_max(12).build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+}
+
+#[test]
+fn test_transmit_noise_exceeded() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["ffffffffffffffff 3c9e00a7 1500059f7f55900035"]);
+
+    let mut t = base_builder().set_noise_max(8).build();
+    let ret = t.transmit(capdu, &mut buf);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(NoSyncByte(9)))");
+}
+
+#[test]
+fn test_negotiate_pps() {
+    set_resp(&hex!["ff119678"]);
+    clear_writes();
+
+    let mut t = transmission();
+    let ret = t.negotiate_pps(0x01, Some(0x96));
+
+    assert_eq!(
+        ret,
+        Ok(PpsResult {
+            accepted: true,
+            fi_di: Some(0x96),
+        })
+    );
+    assert_eq!(get_writes(), [hex!["ff119678"]]);
+}
+
+#[test]
+fn test_negotiate_pps_default_params() {
+    set_resp(&hex!["ff01fe"]);
+
+    let mut t = transmission();
+    let ret = t.negotiate_pps(0x01, Some(0x96));
+
+    assert_eq!(
+        ret,
+        Ok(PpsResult {
+            accepted: false,
+            fi_di: None,
+        })
+    );
+}
+
+#[test]
+fn test_negotiate_pps_mismatch() {
+    set_resp(&hex!["ff11957b"]);
+
+    let mut t = transmission();
+    let ret = t.negotiate_pps(0x01, Some(0x96));
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(PpsMismatch))");
+}
+
+#[test]
+fn test_negotiate_pps_rejected() {
+    set_resp(&hex!["15000015"]);
 
     let mut t = transmission();
+    let ret = t.negotiate_pps(0x01, None);
+
+    assert_eq!(format!("{ret:?}"), "Err(T1(PpsRejected))");
+}
+
+#[test]
+fn test_transmit_crc_big_endian() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f559000bb44"]);
+    clear_writes();
+
+    let mut t = base_builder()
+        .set_chk_algo(ChkAlgo::Crc {
+            little_endian: false,
+        })
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_writes(), [hex!["51000480ca9f7ff6ee"]]);
+}
+
+#[test]
+fn test_transmit_crc_little_endian() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900044bb"]);
+    clear_writes();
+
+    let mut t = base_builder()
+        .set_chk_algo(ChkAlgo::Crc {
+            little_endian: true,
+        })
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_writes(), [hex!["51000480ca9f7feef6"]]);
+}
+
+#[test]
+fn test_transmit_crc_wrong_order() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resp(&hex!["1500059f7f55900044bb"]);
+
+    let mut t = base_builder()
+        .set_chk_algo(ChkAlgo::Crc {
+            little_endian: false,
+        })
+        .build();
     let ret = t.transmit(capdu, &mut buf);
 
     assert!(matches!(ret, Err(Error::T1(_))));
 }
 
 #[test]
-fn test_transmit_too_long() {
+fn test_transmit_cwt_timeout() {
     let mut buf = [0u8; 258];
-    let capdu = &[0u8; 1024];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[
+        &hex!["1500059f7f"],
+        &hex!["1500059f7f"],
+        &hex!["1500059f7f"],
+        &hex!["1500059f7f"],
+    ]);
 
-    let mut t = transmission();
+    let mut t = base_builder()
+        .set_simulated_time(&SIM_TIME)
+        .set_cwt(5)
+        .build();
     let ret = t.transmit(capdu, &mut buf);
 
-    assert!(matches!(ret, Err(Error::T1(_))));
+    assert_eq!(format!("{ret:?}"), "Err(T1(RetriesExhausted(Timeout)))");
+    assert!(ret.is_err_and(|e| e.is_timeout() && !e.is_crc_error()));
+}
+
+#[test]
+fn test_transmit_cwt_retry() {
+    let mut buf = [0u8; 258];
+    let capdu = &hex!["80ca9f7f"];
+    set_resps(&[&hex!["1500059f7f"], &hex!["1500059f7f55900035"]]);
+    clear_writes();
+
+    let mut t = base_builder()
+        .set_simulated_time(&SIM_TIME)
+        .set_cwt(5)
+        .build();
+    let rapdu = t.transmit(capdu, &mut buf).expect("Transmit failed");
+
+    assert_eq!(rapdu, &hex!["9f7f559000"]);
+    assert_eq!(get_writes()[1], hex!["518000d1"]);
 }
 
 const NAD_CARD: u8 = 0x15;
 const NAD_DEV: u8 = 0x51;
+const NAD_CARD_CH1: u8 = 0x25;
+const NAD_DEV_CH1: u8 = 0x52;
+const PPSS: u8 = 0xff;
 
-fn transmission<'a>() -> Transmission<'a, (), ()> {
+fn base_builder() -> TransmissionBuilder<(), (), Set, Set, Set, Set, Set> {
     TransmissionBuilder::new()
         .set_init_cb(open)
         .set_release_cb(close)
-        .set_reset_cb(reset)
         .set_read_cb(read)
         .set_write_cb(write)
         .set_sleep_cb(sleep)
         .set_nad(NAD_CARD, NAD_DEV)
-        .build()
+}
+
+fn transmission() -> Transmission<(), ()> {
+    base_builder().set_reset_cb(reset).build()
+}
+
+/// 300 bytes response chained in 254 and 46 bytes I-blocks
+fn long_resp() -> &'static [&'static [u8]] {
+    Box::leak(Box::new([
+        iblock(0x20, &[0x11; 254]),
+        iblock(0x40, &[0x22; 46]),
+    ]))
+}
+
+fn iblock(pcb: u8, data: &[u8]) -> &'static [u8] {
+    let mut block = vec![NAD_CARD, pcb, data.len() as u8];
+    block.extend_from_slice(data);
+    block.push(block.iter().fold(0, |c, b| c ^ b));
+
+    Box::leak(block.into_boxed_slice())
 }
 
 fn open() -> Result<Option<()>, ()> {
@@ -92,6 +2659,7 @@ fn open() -> Result<Option<()>, ()> {
 }
 
 fn close(_interface: Option<&()>) -> Result<Option<()>, ()> {
+    unsafe { RELEASES += 1 };
     Ok(None)
 }
 
@@ -116,7 +2684,7 @@ fn read(_interface: Option<&()>, buf: &mut [u8]) -> Result<usize, ()> {
 
     if reset_resp {
         set_cnt(0);
-        set_resp(&[]);
+        set_cur_resp(&[]);
     }
 
     Ok(read_len)
@@ -124,22 +2692,75 @@ fn read(_interface: Option<&()>, buf: &mut [u8]) -> Result<usize, ()> {
 
 fn write(_interface: Option<&()>, buf: &[u8]) -> Result<usize, ()> {
     set_cnt(0);
-    if buf[0] != NAD_DEV {
+    if get_resp().is_empty() {
+        next_resp();
+    }
+    if buf[0] != NAD_DEV && buf[0] != NAD_DEV_CH1 && buf[0] != PPSS {
         return Ok(0);
     }
+    set_last_pcb(buf[1]);
+    WRITES.lock().unwrap().push(buf.to_vec());
 
     Ok(buf.len())
 }
 
 fn sleep(_ms: u32) {}
 
+fn yield_now() {
+    unsafe { YIELDS += 1 };
+}
+
+fn now() -> u32 {
+    unsafe {
+        NOW += 1;
+        NOW
+    }
+}
+
 static mut RESP: &[u8] = &[];
+static mut NEXT_RESP: &[&[u8]] = &[];
 static mut READ_CNT: usize = 0;
+static mut LAST_PCB: u8 = 0;
+static mut NOW: u32 = 0;
+static SIM_TIME: SimulatedTime = SimulatedTime::new();
+static mut YIELDS: usize = 0;
+static mut RELEASES: usize = 0;
+static WRITES: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
 
 fn set_resp(resp: &'static [u8]) {
+    unsafe {
+        RESP = resp;
+        NEXT_RESP = &[];
+    };
+}
+
+fn set_resps(resps: &'static [&'static [u8]]) {
+    unsafe {
+        RESP = resps.first().copied().unwrap_or(&[]);
+        NEXT_RESP = resps.get(1..).unwrap_or(&[]);
+    };
+}
+
+fn set_cur_resp(resp: &'static [u8]) {
     unsafe { RESP = resp };
 }
 
+fn next_resp() {
+    let next = unsafe { NEXT_RESP };
+    unsafe {
+        RESP = next.first().copied().unwrap_or(&[]);
+        NEXT_RESP = next.get(1..).unwrap_or(&[]);
+    };
+}
+
+fn set_last_pcb(pcb: u8) {
+    unsafe { LAST_PCB = pcb };
+}
+
+fn get_last_pcb() -> u8 {
+    unsafe { LAST_PCB }
+}
+
 fn set_cnt(cnt: usize) {
     unsafe { READ_CNT = cnt };
 }
@@ -151,3 +2772,27 @@ fn get_resp() -> &'static [u8] {
 fn get_cnt() -> usize {
     unsafe { READ_CNT }
 }
+
+fn set_yields(yields: usize) {
+    unsafe { YIELDS = yields };
+}
+
+fn get_yields() -> usize {
+    unsafe { YIELDS }
+}
+
+fn clear_writes() {
+    WRITES.lock().unwrap().clear();
+}
+
+fn get_writes() -> Vec<Vec<u8>> {
+    WRITES.lock().unwrap().clone()
+}
+
+fn set_releases(releases: usize) {
+    unsafe { RELEASES = releases };
+}
+
+fn get_releases() -> usize {
+    unsafe { RELEASES }
+}